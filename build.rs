@@ -1,11 +1,409 @@
-use std::io::Result;
+//! Compiles the schemas of the schema based formats. Besides cargo this needs
+//! protoc, the capnp tool and flatc on `PATH`, or protoc and flatc at `$PROTOC` and
+//! `$FLATC`. flatc has to match the version of the flatbuffers crate, e.g. flatc
+//! 25.2 for flatbuffers 25.2, since the code it generates is built against the crate
+
+use std::collections::HashMap;
+use std::io::{BufReader, Error, ErrorKind, Result};
+use std::path::Path;
+use std::process::Command;
+
+use capnp::message::ReaderOptions;
+use capnp::schema_capnp::{code_generator_request, node};
+use heck::{ToSnakeCase, ToUpperCamelCase};
+use prost::Message;
+use prost_types::field_descriptor_proto::{Label, Type};
+use prost_types::{DescriptorProto, FieldDescriptorProto, FileDescriptorSet};
+
 fn main() -> Result<()> {
-    prost_build::compile_protos(&["src/test.proto"], &["src/"])?;
+    let out_dir = std::path::PathBuf::from(std::env::var("OUT_DIR").unwrap());
+    let descriptor_set = out_dir.join("file_descriptor_set.bin");
+    prost_build::Config::new()
+        .file_descriptor_set_path(&descriptor_set)
+        .compile_protos(&["src/test.proto"], &["src/"])?;
+    proto_streams(&descriptor_set, &out_dir.join("proto_streams.rs"))?;
 
+    println!("cargo:rerun-if-changed=src/test.capnp");
+    let code_generator_request = out_dir.join("capnp_request.bin");
     capnpc::CompilerCommand::new()
         .file("src/test.capnp")
+        .raw_code_generator_request_path(&code_generator_request)
         .run()
         .expect("compiling schema");
+    capnp_streams(&code_generator_request, &out_dir.join("capnp_streams.rs"))?;
+    flatc("src/test.fbs", &out_dir)?;
 
     Ok(())
 }
+
+/// Writes a table of the top level `<Stream>List` structs of the schemas capnpc
+/// compiled, as listed by the code generator request it was given, for their roots
+/// to be built and read through capnp's dynamic reflection
+fn capnp_streams(code_generator_request: &Path, out: &Path) -> Result<()> {
+    let (schemas, lists) =
+        capnp_lists(code_generator_request).map_err(|e| Error::other(e.to_string()))?;
+
+    let streams: Vec<String> = lists.iter().map(|(s, _)| format!("{:?}", s)).collect();
+    let init: String = lists
+        .iter()
+        .map(|(stream, module)| {
+            format!(
+                "        {:?} => Some(message.init_root::<crate::{}::Builder>().into()),\n",
+                stream, module
+            )
+        })
+        .collect();
+    let get: String = lists
+        .iter()
+        .map(|(stream, module)| {
+            format!(
+                "        {:?} => Some(message.get_root::<crate::{}::Reader>().map(Into::into)),\n",
+                stream, module
+            )
+        })
+        .collect();
+
+    let code = format!(
+        "/// Streams with a list struct in {schemas}
+pub const STREAMS: &[&str] = &[{streams}];
+
+pub fn init_root<'a>(
+    message: &'a mut ::capnp::message::Builder<::capnp::message::HeapAllocator>,
+    stream: &str,
+) -> Option<::capnp::dynamic_value::Builder<'a>> {{
+    match stream {{
+{init}        _ => None,
+    }}
+}}
+
+pub fn get_root<'a, S: ::capnp::message::ReaderSegments>(
+    message: &'a ::capnp::message::Reader<S>,
+    stream: &str,
+) -> Option<::capnp::Result<::capnp::dynamic_value::Reader<'a>>> {{
+    match stream {{
+{get}        _ => None,
+    }}
+}}
+",
+        streams = streams.join(", "),
+    );
+
+    std::fs::write(out, code)
+}
+
+/// Schemas of a code generator request, and its top level `<Stream>List` structs
+/// along with the paths of the modules capnpc generates for them
+fn capnp_lists(code_generator_request: &Path) -> capnp::Result<(String, Vec<(String, String)>)> {
+    let file = std::fs::File::open(code_generator_request)?;
+    let message = capnp::serialize::read_message(BufReader::new(file), ReaderOptions::new())?;
+    let request = message.get_root::<code_generator_request::Reader>()?;
+    let nodes = request.get_nodes()?;
+    let node = |id| nodes.iter().find(|n| n.get_id() == id);
+
+    let mut schemas = vec![];
+    let mut lists = vec![];
+    for file in request.get_requested_files()? {
+        let filename = file.get_filename()?.to_str()?;
+        schemas.push(format!("`{}`", filename));
+        // capnpc names the module of a schema after its file, e.g. `test_capnp`
+        let stem = Path::new(filename).file_stem().unwrap_or_default();
+        let module = format!("{}_capnp", stem.to_string_lossy().replace('-', "_"));
+
+        let Some(file) = node(file.get_id()) else {
+            continue;
+        };
+        for nested in file.get_nested_nodes()? {
+            let name = nested.get_name()?.to_str()?;
+            let is_struct =
+                node(nested.get_id()).is_some_and(|n| matches!(n.which(), Ok(node::Struct(_))));
+            if let (Some(stream), true) = (name.strip_suffix("List"), is_struct) {
+                lists.push((
+                    snake_case(stream),
+                    format!("{}::{}", module, snake_case(name)),
+                ));
+            }
+        }
+    }
+
+    Ok((schemas.join(", "), lists))
+}
+
+/// Writes the modules prost generated for the packages of a compiled descriptor set,
+/// along with a macro naming the generated message of each top level `<stream>List`
+/// message and the conversions of their points. Streams are picked like
+/// `proto::list_messages` does at runtime, a stream with list messages in several
+/// packages being an error
+fn proto_streams(descriptor_set: &Path, out: &Path) -> Result<()> {
+    let descriptor_set = std::fs::read(descriptor_set)?;
+    let descriptor_set = FileDescriptorSet::decode(descriptor_set.as_slice())
+        .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+
+    let mut types = HashMap::new();
+    for file in descriptor_set.file.iter() {
+        let module = file.package().replace('.', "_");
+        message_types(
+            &format!(".{}", file.package()),
+            &module,
+            &file.message_type,
+            &mut types,
+        );
+    }
+
+    let mut packages: Vec<&str> = vec![];
+    let mut lists: Vec<(&str, String)> = vec![];
+    let mut points: Vec<&str> = vec![];
+    let mut impls = String::new();
+    for file in descriptor_set.file.iter() {
+        let package = file.package();
+        for message in file.message_type.iter() {
+            let Some(stream) = message.name().strip_suffix("List") else {
+                continue;
+            };
+            if lists.iter().any(|(s, _)| *s == stream) {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("stream {} has list messages in several packages", stream),
+                ));
+            }
+            if package.is_empty() {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("list message {} isn't in a package", message.name()),
+                ));
+            }
+
+            let point = message
+                .field
+                .iter()
+                .find(|f| f.name() == "messages")
+                .filter(|f| f.label() == Label::Repeated && f.r#type() == Type::Message)
+                .and_then(|f| types.get(f.type_name()).map(|t| (f.type_name(), t)));
+            let Some((point, (point_type, point_message))) = point else {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!(
+                        "list message {} has no repeated messages field",
+                        message.name()
+                    ),
+                ));
+            };
+            if !points.contains(&point) {
+                points.push(point);
+                impls += &point_impl(point_type, point_message);
+            }
+
+            let list = format!(".{}.{}", package, message.name());
+            lists.push((stream, format!("streams::{}", types[&list].0)));
+        }
+        if !packages.contains(&package) {
+            packages.push(package);
+        }
+    }
+
+    let modules: String = packages
+        .iter()
+        .filter(|p| !p.is_empty())
+        .map(|package| {
+            format!(
+                "pub mod {} {{\n    include!(concat!(env!(\"OUT_DIR\"), \"/{}.rs\"));\n}}\n\n",
+                package.replace('.', "_"),
+                package
+            )
+        })
+        .collect();
+    let arms: String = lists
+        .iter()
+        .map(|(stream, message)| {
+            format!(
+                "            {:?} => {{\n                type $L = {};\n                $body\n            }}\n",
+                stream, message
+            )
+        })
+        .collect();
+
+    let code = format!(
+        "{modules}{impls}/// Evaluates `$body` with `$L` being the generated list message of a stream
+macro_rules! with_list_type {{
+    ($stream:expr, $L:ident => $body:expr) => {{
+        match $stream {{
+{arms}            stream => Err(Error::UnknownStream(stream.to_owned())),
+        }}
+    }};
+}}
+"
+    );
+
+    std::fs::write(out, code)
+}
+
+/// Adds the path prost generates for each message, relative to the modules of the
+/// packages, by the message's full name
+fn message_types<'a>(
+    prefix: &str,
+    module: &str,
+    messages: &'a [DescriptorProto],
+    types: &mut HashMap<String, (String, &'a DescriptorProto)>,
+) {
+    for message in messages {
+        let name = format!("{}.{}", prefix, message.name());
+        let path = format!("{}::{}", module, message.name().to_upper_camel_case());
+        let nested = format!("{}::{}", module, rust_field(message.name()));
+        message_types(&name, &nested, &message.nested_type, types);
+        types.insert(name, (path, message));
+    }
+}
+
+/// Implementation of `proto::PointMessage` for the generated message of a point,
+/// each field being set from and read into the payload key of the same name
+fn point_impl(path: &str, message: &DescriptorProto) -> String {
+    let unsupported = message.field.iter().find(|f| !is_scalar(f));
+    let (from_point, into_point) = match unsupported {
+        Some(field) => {
+            let error = format!(
+                "        Err(super::type_mismatch(stream, {:?}, \"a scalar\", {:?}))\n",
+                field.name(),
+                format!("{:?}", field.r#type()).to_lowercase()
+            );
+            (error.clone(), error)
+        }
+        None => {
+            let fields: String = message
+                .field
+                .iter()
+                .map(|f| {
+                    format!(
+                        "            {}: super::field(stream, point, {:?})?,\n",
+                        rust_field(f.name()),
+                        f.name()
+                    )
+                })
+                .collect();
+            let set: String = message
+                .field
+                .iter()
+                .map(|f| {
+                    format!(
+                        "        super::set_point_field(stream, &mut point, &mut payload, {:?}, self.{}.into())?;\n",
+                        f.name(),
+                        rust_field(f.name())
+                    )
+                })
+                .collect();
+            (
+                format!("        Ok(Self {{\n{}        }})\n", fields),
+                format!(
+                    "        let mut point = crate::base::Payload::default();
+        let mut payload = ::serde_json::Map::new();
+{}        point.payload = ::serde_json::Value::Object(payload);
+        Ok(point)
+",
+                    set
+                ),
+            )
+        }
+    };
+
+    format!(
+        "impl super::PointMessage for {path} {{
+    fn from_point(stream: &str, point: &crate::base::Payload) -> Result<Self, super::Error> {{
+{from_point}    }}
+
+    fn into_point(self, stream: &str) -> Result<crate::base::Payload, super::Error> {{
+{into_point}    }}
+}}
+
+"
+    )
+}
+
+/// Whether prost generates a plain scalar for a field, which a payload value
+/// converts to
+fn is_scalar(field: &FieldDescriptorProto) -> bool {
+    let plain =
+        field.label() != Label::Repeated && !field.proto3_optional() && field.oneof_index.is_none();
+
+    plain
+        && matches!(
+            field.r#type(),
+            Type::Double
+                | Type::Float
+                | Type::Int32
+                | Type::Sint32
+                | Type::Sfixed32
+                | Type::Int64
+                | Type::Sint64
+                | Type::Sfixed64
+                | Type::Uint32
+                | Type::Fixed32
+                | Type::Uint64
+                | Type::Fixed64
+                | Type::Bool
+                | Type::String
+        )
+}
+
+/// Identifier prost gives a field, or the module of a message's nested types
+fn rust_field(name: &str) -> String {
+    let mut ident = name.to_snake_case();
+    match ident.as_str() {
+        "as" | "break" | "const" | "continue" | "else" | "enum" | "false" | "fn" | "for" | "if"
+        | "impl" | "in" | "let" | "loop" | "match" | "mod" | "move" | "mut" | "pub" | "ref"
+        | "return" | "static" | "struct" | "trait" | "true" | "type" | "unsafe" | "use"
+        | "where" | "while" | "dyn" | "abstract" | "become" | "box" | "do" | "final" | "macro"
+        | "override" | "priv" | "typeof" | "unsized" | "virtual" | "yield" | "async" | "await"
+        | "try" => ident.insert_str(0, "r#"),
+        // keywords that can't be raw identifiers
+        "self" | "super" | "extern" | "crate" => ident.push('_'),
+        _ => {}
+    }
+
+    ident
+}
+
+/// Generates Rust code for a flatbuffers schema into `<schema>_generated.rs` with
+/// flatc, `$FLATC` if set
+fn flatc(schema: &str, out_dir: &Path) -> Result<()> {
+    println!("cargo:rerun-if-changed={}", schema);
+    println!("cargo:rerun-if-env-changed=FLATC");
+
+    let flatc = std::env::var_os("FLATC").unwrap_or_else(|| "flatc".into());
+    let status = Command::new(&flatc)
+        .arg("--rust")
+        .arg("-o")
+        .arg(out_dir)
+        .arg(schema)
+        .status()
+        .map_err(|e| {
+            let message = match e.kind() {
+                ErrorKind::NotFound => format!(
+                    "{} not found, install flatc from https://github.com/google/flatbuffers \
+                     or set FLATC to its path",
+                    flatc.to_string_lossy()
+                ),
+                _ => format!("running {}: {}", flatc.to_string_lossy(), e),
+            };
+            Error::new(e.kind(), message)
+        })?;
+    if !status.success() {
+        return Err(Error::other(format!("flatc failed to compile {}", schema)));
+    }
+
+    Ok(())
+}
+
+/// Name capnpc gives the module of a struct, e.g. `GpsList` is `gps_list`
+fn snake_case(name: &str) -> String {
+    let mut snake = String::with_capacity(name.len() + 2);
+    for (i, c) in name.chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 {
+                snake.push('_');
+            }
+            snake.push(c.to_ascii_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+
+    snake
+}
@@ -18,6 +18,13 @@ pub struct SimulatorConfig {
     pub num_devices: u32,
     /// path to directory containing files with gps paths to be used in simulation
     pub gps_paths: String,
+    /// batch sizes into which the same simulated points are collected before being flushed
+    pub batch_sizes: Vec<usize>,
+    /// streams to be generated, e.g. gps, imu, bms, peripherals, motor, can and shadow
+    pub streams: Vec<String>,
+    /// seed from which all simulated data is derived, a simulation with the
+    /// same seed and config generates exactly the same points
+    pub seed: Option<u64>,
 }
 
 pub trait Point: Send + std::fmt::Debug {
@@ -90,7 +97,7 @@ impl Stream {
     ) -> Stream {
         let name = Arc::new(stream.into());
         let topic = Arc::new(topic.into());
-        let buffer = Buffer::new(name.clone(), topic.clone());
+        let buffer = Buffer::new(name.clone(), topic.clone(), max_buffer_size);
         let flush_period = Duration::from_secs(DEFAULT_TIMEOUT);
 
         Stream {
@@ -150,7 +157,10 @@ impl Stream {
         let topic = self.topic.clone();
         info!("Flushing stream name: {}, topic: {}", name, topic);
 
-        std::mem::replace(&mut self.buffer, Buffer::new(name, topic))
+        std::mem::replace(
+            &mut self.buffer,
+            Buffer::new(name, topic, self.max_buffer_size),
+        )
     }
 
     /// Fill buffer with data and trigger async channel send on breaching max_buf_size.
@@ -172,16 +182,19 @@ impl Stream {
 pub struct Buffer<T> {
     pub stream: Arc<String>,
     pub topic: Arc<String>,
+    /// number of elements at which the buffer is flushed
+    pub batch_size: usize,
     pub buffer: Vec<T>,
     pub anomalies: String,
     pub anomaly_count: usize,
 }
 
 impl<T> Buffer<T> {
-    pub fn new(stream: Arc<String>, topic: Arc<String>) -> Buffer<T> {
+    pub fn new(stream: Arc<String>, topic: Arc<String>, batch_size: usize) -> Buffer<T> {
         Buffer {
             stream,
             topic,
+            batch_size,
             buffer: vec![],
             anomalies: String::with_capacity(100),
             anomaly_count: 0,
@@ -229,7 +242,11 @@ impl Clone for Stream {
             last_sequence: 0,
             last_timestamp: 0,
             max_buffer_size: self.max_buffer_size,
-            buffer: Buffer::new(self.buffer.stream.clone(), self.buffer.topic.clone()),
+            buffer: Buffer::new(
+                self.buffer.stream.clone(),
+                self.buffer.topic.clone(),
+                self.max_buffer_size,
+            ),
             tx: self.tx.clone(),
             flush_period: self.flush_period,
         }
@@ -0,0 +1,246 @@
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand};
+
+use crate::{
+    base::SimulatorConfig,
+    compress,
+    results::ResultFormat,
+    serialization::{FieldRead, ProtoSchema},
+};
+
+/// Benchmark serialization formats and compression codecs on simulated device data
+#[derive(Debug, Parser)]
+#[command(name = "zerde", version)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the simulator and measure every selected format & codec on its output
+    Bench(BenchArgs),
+    /// Summarize benchmark results into statistics and charts
+    Report(ReportArgs),
+    /// Run the simulator and save its output as a dataset that can be replayed by bench
+    Record(RecordArgs),
+    /// Find the best trade-offs between size and cpu time in benchmark results
+    Recommend(RecommendArgs),
+    /// Project the data a fleet of devices sends per day and month from benchmark results
+    Project(ProjectArgs),
+    /// Check that every selected format decodes simulated points back to the original
+    Verify(VerifyArgs),
+    /// Run the simulator and write its batches in a format into a file of frames
+    Encode(EncodeArgs),
+    /// Read a file written by encode back and summarize its batches per stream
+    Decode(DecodeArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct SimulatorArgs {
+    /// batch sizes to be measured, every size is fed the same simulated points
+    #[arg(short, long, value_delimiter = ',', default_values_t = [1])]
+    pub batch_sizes: Vec<usize>,
+    /// streams to be generated by the simulator
+    #[arg(
+        short,
+        long,
+        value_delimiter = ',',
+        value_parser = ["gps", "imu", "bms", "peripherals", "motor", "can", "shadow"],
+        default_values = ["gps", "imu", "bms", "peripherals", "motor", "can", "shadow"]
+    )]
+    pub streams: Vec<String>,
+    /// number of devices to be simulated
+    #[arg(short = 'n', long, default_value_t = 1)]
+    pub devices: u32,
+    /// directory containing files with gps paths to be used in simulation
+    #[arg(short, long, default_value = "./paths")]
+    pub paths: String,
+    /// seed for a deterministic simulation, points are random if not set
+    #[arg(long)]
+    pub seed: Option<u64>,
+}
+
+impl SimulatorArgs {
+    pub fn config(&self) -> SimulatorConfig {
+        SimulatorConfig {
+            num_devices: self.devices,
+            gps_paths: self.paths.clone(),
+            batch_sizes: self.batch_sizes.clone(),
+            streams: self.streams.clone(),
+            seed: self.seed,
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct ProtoArgs {
+    /// .proto file from which the protoref format's messages are compiled at runtime,
+    /// the schema built into zerde is used if neither this nor a descriptor set is set
+    #[arg(long, conflicts_with = "descriptor_set")]
+    pub proto: Option<PathBuf>,
+    /// directories in which imports of the .proto file are looked up
+    #[arg(long, requires = "proto")]
+    pub proto_include: Vec<PathBuf>,
+    /// precompiled FileDescriptorSet from which the protoref format's messages are read
+    #[arg(long)]
+    pub descriptor_set: Option<PathBuf>,
+}
+
+impl ProtoArgs {
+    pub fn schema(&self) -> ProtoSchema {
+        match (&self.proto, &self.descriptor_set) {
+            (Some(path), _) => ProtoSchema::Proto {
+                path: path.clone(),
+                includes: self.proto_include.clone(),
+            },
+            (None, Some(path)) => ProtoSchema::DescriptorSet(path.clone()),
+            (None, None) => ProtoSchema::Embedded,
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct BenchArgs {
+    /// serialization formats to be measured, all registered formats if not set
+    #[arg(short, long, value_delimiter = ',')]
+    pub formats: Vec<String>,
+    /// compression codecs to be applied on top of every format
+    #[arg(short, long, value_enum, value_delimiter = ',', default_values_t = compress::Algo::ALL)]
+    pub codecs: Vec<compress::Algo>,
+    #[command(flatten)]
+    pub proto: ProtoArgs,
+    #[command(flatten)]
+    pub simulator: SimulatorArgs,
+    /// measure batches from a recorded dataset instead of running the simulator,
+    /// simulator options are then ignored
+    #[arg(long)]
+    pub replay: Option<PathBuf>,
+    /// directory into which result files are written
+    #[arg(short, long, default_value = "./data")]
+    pub output: PathBuf,
+    /// file format of the results, one record per measurement
+    #[arg(short, long, value_enum, default_value_t = ResultFormat::Csv)]
+    pub results: ResultFormat,
+    /// untimed runs of every operation on a batch, before it is timed
+    #[arg(short, long, default_value_t = 3)]
+    pub warmup: usize,
+    /// timed runs of every operation on a batch, the time of each is recorded
+    #[arg(long, default_value_t = 10)]
+    pub repetitions: usize,
+    /// numeric fields read from every serialized batch without decoding it whole, as
+    /// `<field>:<sum|max>`. Fields a stream doesn't have are skipped
+    #[arg(long, value_delimiter = ',', default_value = "timestamp:max")]
+    pub reads: Vec<FieldRead>,
+    /// stop the run after these many seconds, runs forever if not set
+    #[arg(short = 't', long)]
+    pub duration: Option<u64>,
+}
+
+#[derive(Debug, Args)]
+pub struct ReportArgs {
+    /// results file written by a benchmark run, csv or jsonl
+    #[arg(short, long, default_value = "./data/results.csv")]
+    pub input: PathBuf,
+    /// directory into which analysis files and charts are written
+    #[arg(short, long, default_value = "./data")]
+    pub output: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct RecommendArgs {
+    /// results file written by a benchmark run, csv or jsonl
+    #[arg(short, long, default_value = "./data/results.csv")]
+    pub input: PathBuf,
+    /// directory into which the pareto frontier is written
+    #[arg(short, long, default_value = "./data")]
+    pub output: PathBuf,
+    /// bytes per second a device may send, unlimited if not set
+    #[arg(long)]
+    pub bandwidth: Option<f64>,
+    /// percent of a cpu core a device may spend on serialization and compression,
+    /// unlimited if not set
+    #[arg(long)]
+    pub cpu: Option<f64>,
+}
+
+#[derive(Debug, Args)]
+pub struct ProjectArgs {
+    /// results file written by a benchmark run, csv or jsonl
+    #[arg(short, long, default_value = "./data/results.csv")]
+    pub input: PathBuf,
+    /// directory into which the projection is written
+    #[arg(short, long, default_value = "./data")]
+    pub output: PathBuf,
+    /// number of devices in the fleet
+    #[arg(short = 'n', long, default_value_t = 1)]
+    pub devices: u64,
+    /// price of sending a megabyte, costs aren't projected if not set
+    #[arg(long)]
+    pub price_per_mb: Option<f64>,
+}
+
+#[derive(Debug, Args)]
+pub struct RecordArgs {
+    #[command(flatten)]
+    pub simulator: SimulatorArgs,
+    /// file into which simulated batches are written
+    #[arg(short, long, default_value = "./data/dataset.jsonl")]
+    pub output: PathBuf,
+    /// stop recording after these many seconds, runs forever if not set
+    #[arg(short = 't', long)]
+    pub duration: Option<u64>,
+}
+
+#[derive(Debug, Args)]
+pub struct VerifyArgs {
+    /// serialization formats to be checked, all registered formats if not set
+    #[arg(short, long, value_delimiter = ',')]
+    pub formats: Vec<String>,
+    #[command(flatten)]
+    pub proto: ProtoArgs,
+    #[command(flatten)]
+    pub simulator: SimulatorArgs,
+    /// check batches from a recorded dataset instead of running the simulator
+    #[arg(long)]
+    pub replay: Option<PathBuf>,
+    /// largest absolute difference at which decoded numbers still count as equal
+    #[arg(long, default_value_t = 1e-9)]
+    pub tolerance: f64,
+    /// number of batches to be checked
+    #[arg(long, default_value_t = 100)]
+    pub batches: usize,
+}
+
+#[derive(Debug, Args)]
+pub struct EncodeArgs {
+    /// serialization format the batches are written in
+    #[arg(short, long, default_value = "json")]
+    pub format: String,
+    #[command(flatten)]
+    pub proto: ProtoArgs,
+    #[command(flatten)]
+    pub simulator: SimulatorArgs,
+    /// encode batches from a recorded dataset instead of running the simulator
+    #[arg(long)]
+    pub replay: Option<PathBuf>,
+    /// file into which batches are written, one length-delimited frame per batch
+    #[arg(short, long, default_value = "./data/batches.frames")]
+    pub output: PathBuf,
+    /// number of batches to be written
+    #[arg(long, default_value_t = 100)]
+    pub batches: usize,
+}
+
+#[derive(Debug, Args)]
+pub struct DecodeArgs {
+    /// serialization format the batches were written in
+    #[arg(short, long, default_value = "json")]
+    pub format: String,
+    #[command(flatten)]
+    pub proto: ProtoArgs,
+    /// file written by encode
+    #[arg(short, long, default_value = "./data/batches.frames")]
+    pub input: PathBuf,
+}
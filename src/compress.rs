@@ -1,4 +1,5 @@
 use std::{
+    fmt::Display,
     io::{Read, Write},
     time::Instant,
 };
@@ -17,7 +18,7 @@ pub enum Error {
     Snap(Box<snap::write::IntoInnerError<snap::write::FrameEncoder<Vec<u8>>>>),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
 pub enum Algo {
     Lz4,
     Snappy,
@@ -25,7 +26,20 @@ pub enum Algo {
     Zstd,
 }
 
+impl Display for Algo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Lz4 => f.write_str("lz4"),
+            Self::Snappy => f.write_str("snappy"),
+            Self::Zlib => f.write_str("zlib"),
+            Self::Zstd => f.write_str("zstd"),
+        }
+    }
+}
+
 impl Algo {
+    pub const ALL: [Algo; 4] = [Self::Lz4, Self::Snappy, Self::Zlib, Self::Zstd];
+
     pub async fn compress(&self, payload: &mut Vec<u8>, topic: &mut String) -> Result<u128, Error> {
         let now = Instant::now();
         match self {
@@ -35,7 +49,7 @@ impl Algo {
             Self::Zstd => Self::zstd_compress(payload, topic).await?,
         }
 
-        Ok(now.elapsed().as_micros())
+        Ok(now.elapsed().as_nanos())
     }
 
     pub async fn decompress(
@@ -51,7 +65,7 @@ impl Algo {
             Self::Zstd => Self::zstd_decompress(payload, topic).await?,
         }
 
-        Ok(now.elapsed().as_micros())
+        Ok(now.elapsed().as_nanos())
     }
 
     fn lz4_compress(payload: &mut Vec<u8>, topic: &mut String) -> Result<(), Error> {
@@ -0,0 +1,83 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, LineWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+
+use flume::{SendError, Sender};
+use serde::{Deserialize, Serialize};
+
+use crate::base::{Buffer, Payload};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Io error {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Json error {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Send error {0}")]
+    Send(#[from] SendError<Buffer<Payload>>),
+}
+
+/// A buffer as flushed by the simulator, one line of a dataset file
+#[derive(Debug, Serialize, Deserialize)]
+struct Record {
+    stream: String,
+    topic: String,
+    batch_size: usize,
+    points: Vec<Payload>,
+}
+
+/// Writes buffers into a dataset file, one json line per buffer
+pub struct Recorder {
+    file: LineWriter<File>,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> Result<Recorder, Error> {
+        let file = LineWriter::new(File::create(path)?);
+
+        Ok(Recorder { file })
+    }
+
+    pub fn write(&mut self, buffer: &Buffer<Payload>) -> Result<(), Error> {
+        let record = Record {
+            stream: buffer.stream.to_string(),
+            topic: buffer.topic.to_string(),
+            batch_size: buffer.batch_size,
+            points: buffer.buffer.clone(),
+        };
+        serde_json::to_writer(&mut self.file, &record)?;
+        self.file.write_all(b"\n")?;
+
+        Ok(())
+    }
+}
+
+/// Sends every buffer of a dataset file, in recorded order, onto `tx`
+pub fn replay(path: &Path, tx: Sender<Buffer<Payload>>) -> Result<(), Error> {
+    let file = BufReader::new(File::open(path)?);
+
+    for line in file.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let record: Record = serde_json::from_str(&line)?;
+        let stream = Arc::new(record.stream);
+        let mut buffer = Buffer::new(stream.clone(), Arc::new(record.topic), record.batch_size);
+        // stream name isn't part of a serialized point
+        buffer.buffer = record
+            .points
+            .into_iter()
+            .map(|p| Payload {
+                stream: stream.to_string(),
+                ..p
+            })
+            .collect();
+
+        tx.send(buffer)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,250 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+use serde_json::Value;
+
+use crate::base::Payload;
+
+/// How a field of decoded points differs from the original points
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Difference {
+    /// largest absolute error of a numeric field, beyond the tolerance
+    Numeric(f64),
+    /// non numeric value changed
+    Changed,
+    /// field is absent from decoded points
+    Missing,
+    /// field is only present in decoded points
+    Unexpected,
+}
+
+impl Difference {
+    fn merge(self, other: Difference) -> Difference {
+        match (self, other) {
+            (Self::Numeric(a), Self::Numeric(b)) => Self::Numeric(a.max(b)),
+            (a, b) if a == b => a,
+            (Self::Missing, _) | (_, Self::Missing) => Self::Missing,
+            _ => Self::Changed,
+        }
+    }
+}
+
+impl Display for Difference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Numeric(error) => write!(f, "max error {}", error),
+            Self::Changed => f.write_str("changed"),
+            Self::Missing => f.write_str("missing"),
+            Self::Unexpected => f.write_str("unexpected"),
+        }
+    }
+}
+
+/// Outcome of comparing points decoded by a format with the points it encoded
+#[derive(Debug, Clone, PartialEq)]
+pub enum Fidelity {
+    Lossless,
+    /// differing fields, by name
+    Lossy(BTreeMap<String, Difference>),
+    /// points couldn't be encoded or decoded, or their number changed
+    Failed(String),
+}
+
+impl Fidelity {
+    /// Combines the outcomes of two checks of the same format, keeping the worst
+    pub fn merge(self, other: Fidelity) -> Fidelity {
+        match (self, other) {
+            (Self::Failed(e), _) | (_, Self::Failed(e)) => Self::Failed(e),
+            (Self::Lossless, f) | (f, Self::Lossless) => f,
+            (Self::Lossy(mut a), Self::Lossy(b)) => {
+                for (field, difference) in b {
+                    merge_difference(&mut a, field, difference);
+                }
+                Self::Lossy(a)
+            }
+        }
+    }
+}
+
+impl Display for Fidelity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Lossless => f.write_str("lossless"),
+            Self::Lossy(differences) => {
+                f.write_str("lossy")?;
+                for (i, (field, difference)) in differences.iter().enumerate() {
+                    let separator = if i == 0 { ": " } else { ", " };
+                    write!(f, "{}{} ({})", separator, field, difference)?;
+                }
+                Ok(())
+            }
+            Self::Failed(reason) => write!(f, "failed: {}", reason),
+        }
+    }
+}
+
+/// Compares decoded points with the original points, field by field. Numbers
+/// count as equal when they differ by at most `tolerance`
+pub fn check(original: &[Payload], decoded: &[Payload], tolerance: f64) -> Fidelity {
+    if original.len() != decoded.len() {
+        return Fidelity::Failed(format!(
+            "decoded {} of {} points",
+            decoded.len(),
+            original.len()
+        ));
+    }
+
+    let mut differences = BTreeMap::new();
+    for (original, decoded) in original.iter().zip(decoded) {
+        let mut decoded = fields(decoded);
+        for (field, value) in fields(original) {
+            let difference = match decoded.remove(&field) {
+                Some(decoded) => compare(&value, &decoded, tolerance),
+                None => Some(Difference::Missing),
+            };
+            if let Some(difference) = difference {
+                merge_difference(&mut differences, field, difference);
+            }
+        }
+
+        for field in decoded.into_keys() {
+            merge_difference(&mut differences, field, Difference::Unexpected);
+        }
+    }
+
+    match differences.is_empty() {
+        true => Fidelity::Lossless,
+        false => Fidelity::Lossy(differences),
+    }
+}
+
+fn merge_difference(
+    differences: &mut BTreeMap<String, Difference>,
+    field: String,
+    difference: Difference,
+) {
+    differences
+        .entry(field)
+        .and_modify(|d| *d = d.merge(difference))
+        .or_insert(difference);
+}
+
+/// Fields of a point as they appear once serialized, i.e. with the payload flattened
+fn fields(point: &Payload) -> BTreeMap<String, Value> {
+    let mut fields = BTreeMap::new();
+    fields.insert("sequence".to_owned(), Value::from(point.sequence));
+    fields.insert("timestamp".to_owned(), Value::from(point.timestamp));
+
+    match &point.payload {
+        Value::Object(map) => {
+            fields.extend(map.iter().map(|(k, v)| (k.clone(), v.clone())));
+        }
+        Value::Null => {}
+        value => {
+            fields.insert("payload".to_owned(), value.clone());
+        }
+    }
+
+    fields
+}
+
+fn compare(original: &Value, decoded: &Value, tolerance: f64) -> Option<Difference> {
+    match (original.as_f64(), decoded.as_f64()) {
+        // formats may widen or narrow numeric types, e.g. decode an integer as a float
+        (Some(a), Some(b)) => {
+            let error = (a - b).abs();
+            match error > tolerance || (a.is_nan() != b.is_nan()) {
+                true => Some(Difference::Numeric(error)),
+                false => None,
+            }
+        }
+        _ if original == decoded => None,
+        _ => Some(Difference::Changed),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn point(payload: Value) -> Payload {
+        Payload {
+            stream: String::new(),
+            sequence: 1,
+            timestamp: 100,
+            payload,
+        }
+    }
+
+    #[test]
+    fn equal_points_are_lossless() {
+        let points = [point(json!({"voltage": 48.5, "mode": "eco", "rpm": 3000}))];
+
+        assert_eq!(check(&points, &points, 0.0), Fidelity::Lossless);
+    }
+
+    #[test]
+    fn integers_decoded_as_floats_are_lossless() {
+        let original = [point(json!({"rpm": 3000}))];
+        let decoded = [point(json!({"rpm": 3000.0}))];
+
+        assert_eq!(check(&original, &decoded, 0.0), Fidelity::Lossless);
+    }
+
+    #[test]
+    fn float_drift_within_tolerance_is_lossless() {
+        let original = [point(json!({"voltage": 48.1}))];
+        let decoded = [point(json!({"voltage": 48.1f32 as f64}))];
+
+        assert_eq!(check(&original, &decoded, 1e-5), Fidelity::Lossless);
+    }
+
+    #[test]
+    fn float_drift_beyond_tolerance_is_lossy() {
+        let original = [
+            point(json!({"voltage": 48.0})),
+            point(json!({"voltage": 50.0})),
+        ];
+        let decoded = [
+            point(json!({"voltage": 48.25})),
+            point(json!({"voltage": 50.5})),
+        ];
+
+        let Fidelity::Lossy(differences) = check(&original, &decoded, 0.1) else {
+            panic!("drift beyond the tolerance found lossless");
+        };
+        // the largest error of every point
+        assert_eq!(
+            differences,
+            BTreeMap::from([("voltage".to_owned(), Difference::Numeric(0.5))])
+        );
+    }
+
+    #[test]
+    fn missing_and_extra_fields_are_lossy() {
+        let original = [point(json!({"voltage": 48.0, "mode": "eco"}))];
+        let decoded = [point(json!({"voltage": 48.0, "gear": 2}))];
+
+        assert_eq!(
+            check(&original, &decoded, 0.0),
+            Fidelity::Lossy(BTreeMap::from([
+                ("gear".to_owned(), Difference::Unexpected),
+                ("mode".to_owned(), Difference::Missing),
+            ]))
+        );
+    }
+
+    #[test]
+    fn changed_values_and_point_counts() {
+        let original = [point(json!({"mode": "eco"}))];
+        let decoded = [point(json!({"mode": "sport"}))];
+
+        assert_eq!(
+            check(&original, &decoded, 0.0),
+            Fidelity::Lossy(BTreeMap::from([("mode".to_owned(), Difference::Changed)]))
+        );
+        assert!(matches!(check(&original, &[], 0.0), Fidelity::Failed(_)));
+    }
+}
@@ -0,0 +1,524 @@
+//! Benchmarks serialization formats and compression codecs on simulated device data.
+//!
+//! Formats implement [`serialization::Serializer`]. A crate with formats of its own
+//! can add them to [`serialization::Registry::builtin`] and pass that registry to [`run`].
+
+use std::collections::BTreeMap;
+use std::hint::black_box;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+pub mod base;
+pub mod cli;
+pub mod compress;
+pub mod dataset;
+pub mod fidelity;
+pub mod measure;
+pub mod projection;
+pub mod recommend;
+pub mod report;
+pub mod results;
+pub mod serialization;
+pub mod simulator;
+
+mod test_capnp {
+    include!(concat!(env!("OUT_DIR"), "/src/test_capnp.rs"));
+}
+
+pub use base::{Buffer, Payload, SimulatorConfig, Stream};
+use cli::{
+    BenchArgs, Cli, Command, DecodeArgs, EncodeArgs, ProjectArgs, RecommendArgs, RecordArgs,
+    VerifyArgs,
+};
+use fidelity::Fidelity;
+use flume::{bounded, Receiver};
+use log::error;
+use measure::Sampling;
+use results::{BenchRecord, Metric, ResultWriter, NO_CODEC};
+use serialization::{
+    timed, FieldRead, FrameReader, FrameWriter, ProtoReflect, ProtoSchema, Registry, Serializer,
+};
+
+/// Runs a command, `registry` is only built by commands that serialize data. Other
+/// crates can run zerde with formats of their own by adding these to the registry
+pub async fn run(cli: Cli, registry: impl FnOnce() -> Result<Registry, serialization::Error>) {
+    let registry = || match registry() {
+        Ok(registry) => registry,
+        Err(e) => {
+            eprintln!("Format registry error: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    match cli.command {
+        Command::Bench(args) => bench(args, registry()).await,
+        Command::Record(args) => record(args).await,
+        Command::Project(args) => project(args),
+        Command::Recommend(args) => recommend(args),
+        Command::Verify(args) => verify(args, registry()).await,
+        Command::Encode(args) => encode(args, registry()).await,
+        Command::Decode(args) => decode(args, registry()),
+        Command::Report(args) => {
+            if let Err(e) = report::generate(&args.input, &args.output) {
+                eprintln!("Report error: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+fn start_simulator(simulator_config: SimulatorConfig) -> Receiver<Buffer<Payload>> {
+    let (data_tx, data_rx) = bounded(10);
+    std::thread::spawn(move || {
+        match simulator::start(data_tx, &simulator_config) {
+            // receivers are dropped once a command is done with simulated data
+            Ok(()) | Err(simulator::Error::Send(_)) => {}
+            Err(e) => error!("Simulator error: {}", e),
+        }
+    });
+
+    data_rx
+}
+
+fn start_replay(path: PathBuf) -> Receiver<Buffer<Payload>> {
+    let (data_tx, data_rx) = bounded(10);
+    std::thread::spawn(move || {
+        if let Err(e) = dataset::replay(&path, data_tx) {
+            eprintln!("Replay error: {}", e);
+        }
+    });
+
+    data_rx
+}
+
+async fn record(args: RecordArgs) {
+    let data_rx = start_simulator(args.simulator.config());
+    if let Some(dir) = args.output.parent() {
+        std::fs::create_dir_all(dir).unwrap();
+    }
+    let mut recorder = dataset::Recorder::create(&args.output).unwrap();
+
+    let deadline = args
+        .duration
+        .map(|secs| Instant::now() + Duration::from_secs(secs));
+
+    while deadline.is_none_or(|d| Instant::now() < d) {
+        let next = data_rx.recv_async().await.unwrap();
+        recorder.write(&next).unwrap();
+    }
+}
+
+fn recommend(args: RecommendArgs) {
+    let budget = recommend::Budget {
+        bandwidth: args.bandwidth,
+        cpu: args.cpu,
+    };
+    let (frontier, recommendation) = match recommend::generate(&args.input, &args.output, budget) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!("Recommend error: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    for ((stream, batch_size), choices) in frontier {
+        println!("{} at batch size {}:", stream, batch_size);
+        for c in choices {
+            println!(
+                "  {} & {}: {:.0} bytes, {:.0} ns",
+                c.format, c.codec, c.bytes, c.encode_ns
+            );
+        }
+    }
+
+    match recommendation {
+        Some(recommendation) => print!("{}", recommendation),
+        None => println!("No combination was measured for every stream"),
+    }
+}
+
+fn project(args: ProjectArgs) {
+    let fleet = projection::Fleet {
+        devices: args.devices,
+        price_per_mb: args.price_per_mb,
+    };
+    let projections = match projection::generate(&args.input, &args.output, fleet) {
+        Ok(p) => p,
+        Err(e) => {
+            eprintln!("Projection error: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    println!(
+        "Monthly data of {} devices, sending all streams:",
+        args.devices
+    );
+    for p in projections
+        .iter()
+        .filter(|p| p.stream == projection::ALL_STREAMS)
+    {
+        let cost = p
+            .fleet_cost_per_month
+            .map(|c| format!(", costing {:.2}", c))
+            .unwrap_or_default();
+        println!(
+            "  {} & {} at batch size {}: {:.1} MB per device, {:.1} MB for the fleet{}",
+            p.format,
+            p.codec,
+            p.batch_size,
+            p.device_bytes_per_month / 1e6,
+            p.fleet_bytes_per_month / 1e6,
+            cost
+        );
+    }
+}
+
+/// Registers the protoref format with a schema given on the command line, exits if
+/// it can't be loaded
+fn load_proto(registry: &mut Registry, schema: ProtoSchema) {
+    if let ProtoSchema::Embedded = schema {
+        return;
+    }
+
+    match schema.load().and_then(|pool| ProtoReflect::new(&pool)) {
+        Ok(proto_reflect) => registry.register(proto_reflect),
+        Err(e) => {
+            eprintln!("Protobuf schema error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Serializers of the formats named on the command line, exits if any is unknown
+fn select<'a>(registry: &'a Registry, formats: &[String]) -> Vec<&'a dyn Serializer> {
+    match registry.select(formats) {
+        Ok(serializers) => serializers,
+        Err(e) => {
+            eprintln!("{}, expected one of: {}", e, registry.names().join(", "));
+            std::process::exit(1);
+        }
+    }
+}
+
+async fn verify(args: VerifyArgs, mut registry: Registry) {
+    load_proto(&mut registry, args.proto.schema());
+    let serializers = select(&registry, &args.formats);
+    let data_rx = match args.replay.clone() {
+        Some(path) => start_replay(path),
+        None => start_simulator(args.simulator.config()),
+    };
+
+    // keyed by position of the format, to be listed in the selected order
+    let mut checks: BTreeMap<(String, usize), Fidelity> = BTreeMap::new();
+    for _ in 0..args.batches {
+        let Ok(next) = data_rx.recv_async().await else {
+            break;
+        };
+
+        let stream = next.topic.as_str();
+        for (i, serializer) in serializers.iter().enumerate() {
+            if !serializer.supports(stream) {
+                continue;
+            }
+
+            let decoded = serializer
+                .serialize(stream, next.buffer.clone())
+                .and_then(|serialized| serializer.deserialize(stream, &serialized));
+            let fidelity = match decoded {
+                Ok(decoded) => fidelity::check(&next.buffer, &decoded, args.tolerance),
+                Err(e) => Fidelity::Failed(e.to_string()),
+            };
+
+            let key = (stream.to_owned(), i);
+            let fidelity = match checks.remove(&key) {
+                Some(previous) => previous.merge(fidelity),
+                None => fidelity,
+            };
+            checks.insert(key, fidelity);
+        }
+    }
+
+    for ((stream, i), fidelity) in checks {
+        println!("{} {}: {}", stream, serializers[i].name(), fidelity);
+    }
+}
+
+async fn encode(args: EncodeArgs, mut registry: Registry) {
+    load_proto(&mut registry, args.proto.schema());
+    let serializer = select(&registry, std::slice::from_ref(&args.format))[0];
+    let data_rx = match args.replay.clone() {
+        Some(path) => start_replay(path),
+        None => start_simulator(args.simulator.config()),
+    };
+
+    if let Some(dir) = args.output.parent() {
+        std::fs::create_dir_all(dir).unwrap();
+    }
+    let file = tokio::fs::File::create(&args.output).await.unwrap();
+    let mut writer = FrameWriter::new(tokio::io::BufWriter::new(file));
+
+    let (mut batches, mut bytes) = (0, 0);
+    while batches < args.batches {
+        let Ok(next) = data_rx.recv_async().await else {
+            break;
+        };
+        if !serializer.supports(&next.topic) {
+            continue;
+        }
+
+        match writer
+            .write_batch_async(serializer, &next.topic, next.buffer)
+            .await
+        {
+            Ok(len) => bytes += len,
+            Err(e) => {
+                eprintln!("Encode error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        batches += 1;
+    }
+    writer.flush_async().await.unwrap();
+
+    println!(
+        "Wrote {} batches in {}, {} bytes, to {}",
+        batches,
+        serializer.name(),
+        bytes,
+        args.output.display()
+    );
+}
+
+fn decode(args: DecodeArgs, mut registry: Registry) {
+    load_proto(&mut registry, args.proto.schema());
+    let serializer = select(&registry, std::slice::from_ref(&args.format))[0];
+    let file = match std::fs::File::open(&args.input) {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("Decode error: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // batches, points and bytes of every stream
+    let mut streams: BTreeMap<String, (usize, usize, usize)> = BTreeMap::new();
+    for frame in FrameReader::new(std::io::BufReader::new(file)) {
+        let decoded = frame.and_then(|frame| {
+            let points = frame.deserialize(serializer)?;
+            Ok((frame, points))
+        });
+        let (frame, points) = match decoded {
+            Ok(d) => d,
+            Err(e) => {
+                eprintln!("Decode error: {}", e);
+                std::process::exit(1);
+            }
+        };
+
+        let counts = streams.entry(frame.stream).or_default();
+        counts.0 += 1;
+        counts.1 += points.len();
+        counts.2 += frame.payload.len();
+    }
+
+    for (stream, (batches, points, bytes)) in streams {
+        println!(
+            "{}: {} batches, {} points, {} bytes",
+            stream, batches, points, bytes
+        );
+    }
+}
+
+async fn bench(args: BenchArgs, mut registry: Registry) {
+    load_proto(&mut registry, args.proto.schema());
+    let serializers = select(&registry, &args.formats);
+    let data_rx = match args.replay.clone() {
+        Some(path) => start_replay(path),
+        None => start_simulator(args.simulator.config()),
+    };
+
+    let sampling = Sampling {
+        warmup: args.warmup,
+        repetitions: args.repetitions,
+    };
+
+    std::fs::create_dir_all(&args.output).unwrap();
+    let mut results = ResultWriter::create(&args.output, args.results).unwrap();
+
+    // Checked between batches, as measuring a batch never yields back to a timer
+    let deadline = args
+        .duration
+        .map(|secs| Instant::now() + Duration::from_secs(secs));
+
+    while deadline.is_none_or(|d| Instant::now() < d) {
+        // Replayed datasets end, closing the channel
+        let Ok(next) = data_rx.recv_async().await else {
+            break;
+        };
+        let records = serz(
+            &serializers,
+            &args.codecs,
+            &sampling,
+            &args.reads,
+            next.topic.as_str(),
+            next.batch_size,
+            next.buffer,
+        )
+        .await;
+        for record in records.iter() {
+            results.write(record).unwrap();
+        }
+        results.flush().unwrap();
+    }
+}
+
+async fn serz(
+    serializers: &[&dyn Serializer],
+    codecs: &[compress::Algo],
+    sampling: &Sampling,
+    reads: &[FieldRead],
+    original_topic: &str,
+    batch_size: usize,
+    original_payload: Vec<Payload>,
+) -> Vec<BenchRecord> {
+    let mut records = vec![];
+    let record = |format: &str, codec: &str, metric: Metric, value: u64| BenchRecord {
+        stream: original_topic.to_owned(),
+        batch_size,
+        format: format.to_owned(),
+        codec: codec.to_owned(),
+        metric,
+        value,
+    };
+    // fields the stream doesn't have can't be read from any format
+    let reads: Vec<FieldRead> = reads
+        .iter()
+        .filter(|r| r.applies(&original_payload))
+        .cloned()
+        .collect();
+
+    for serializer in serializers {
+        if !serializer.supports(original_topic) {
+            continue;
+        }
+
+        let format = serializer.name();
+        // a batch that a format can't handle is left out of its measurements
+        let serialized = sampling.run(|| {
+            // serializers take their batch, which is cloned outside of the measured time
+            let batch = original_payload.clone();
+            timed(move || serializer.serialize(original_topic, black_box(batch)))
+        });
+        let (serialized_payload, serialization_times) = match serialized {
+            Ok(s) => s,
+            Err(e) => {
+                error!("{} failed to serialize {}: {}", format, original_topic, e);
+                continue;
+            }
+        };
+
+        records.extend(
+            serialization_times
+                .into_iter()
+                .map(|t| record(format, NO_CODEC, Metric::SerializeNs, t)),
+        );
+        records.push(record(
+            format,
+            NO_CODEC,
+            Metric::Bytes,
+            serialized_payload.len() as u64,
+        ));
+
+        for algo in codecs {
+            let (compression_times, compressed_len, decompression_times) =
+                z(*algo, sampling, &serialized_payload, original_topic)
+                    .await
+                    .unwrap();
+            let codec = algo.to_string();
+            records.extend(
+                compression_times
+                    .into_iter()
+                    .map(|t| record(format, &codec, Metric::CompressNs, t)),
+            );
+            records.push(record(format, &codec, Metric::Bytes, compressed_len as u64));
+            records.extend(
+                decompression_times
+                    .into_iter()
+                    .map(|t| record(format, &codec, Metric::DecompressNs, t)),
+            );
+        }
+
+        let deserialized = sampling.run(|| {
+            timed(|| serializer.deserialize(original_topic, black_box(&serialized_payload)))
+        });
+        let (_deserialized_payload, deserialization_times) = match deserialized {
+            Ok(d) => d,
+            Err(e) => {
+                error!("{} failed to deserialize {}: {}", format, original_topic, e);
+                continue;
+            }
+        };
+        records.extend(
+            deserialization_times
+                .into_iter()
+                .map(|t| record(format, NO_CODEC, Metric::DeserializeNs, t)),
+        );
+
+        if reads.is_empty() {
+            continue;
+        }
+        let read = sampling.run(|| {
+            timed(|| serializer.read_fields(original_topic, black_box(&serialized_payload), &reads))
+        });
+        match read {
+            Ok((_, read_times)) => records.extend(
+                read_times
+                    .into_iter()
+                    .map(|t| record(format, NO_CODEC, Metric::ReadNs, t)),
+            ),
+            Err(e) => error!(
+                "{} failed to read fields of {}: {}",
+                format, original_topic, e
+            ),
+        }
+    }
+
+    records
+}
+
+async fn z(
+    algo: compress::Algo,
+    sampling: &Sampling,
+    original_payload: &Vec<u8>,
+    original_topic: &str,
+) -> Result<(Vec<u64>, usize, Vec<u64>), compress::Error> {
+    let ((compressed_payload, compressed_topic), compression_times) = sampling
+        .run_async(|| async move {
+            let mut compressed_payload = black_box(original_payload.clone());
+            let mut compressed_topic = original_topic.to_owned();
+            let time = algo
+                .compress(&mut compressed_payload, &mut compressed_topic)
+                .await?;
+            Ok::<_, compress::Error>(((compressed_payload, compressed_topic), time))
+        })
+        .await?;
+
+    // println!("compressed: {:?}", compressed_payload);
+    let compressed_len = compressed_payload.len();
+
+    let (decompressed_payload, decompression_times) = sampling
+        .run_async(|| async {
+            let mut decompressed_payload = black_box(compressed_payload.clone());
+            let mut decompressed_topic = compressed_topic.clone();
+            let time = algo
+                .decompress(&mut decompressed_payload, &mut decompressed_topic)
+                .await?;
+            Ok::<_, compress::Error>((decompressed_payload, time))
+        })
+        .await?;
+
+    assert_eq!(original_payload, &decompressed_payload);
+
+    Ok((compression_times, compressed_len, decompression_times))
+}
@@ -0,0 +1,54 @@
+use std::future::Future;
+use std::hint::black_box;
+
+/// Number of untimed and timed runs of every measured operation
+#[derive(Debug, Clone, Copy)]
+pub struct Sampling {
+    pub warmup: usize,
+    pub repetitions: usize,
+}
+
+impl Sampling {
+    /// Runs `f`, which returns its output along with the nanoseconds it took, `warmup`
+    /// times without and then `repetitions` times with recording the time taken.
+    /// Returns output of the last run along with the time of every timed run, so that
+    /// statistics describe the spread across repetitions rather than a single value
+    pub fn run<T, E>(
+        &self,
+        mut f: impl FnMut() -> Result<(T, u128), E>,
+    ) -> Result<(T, Vec<u64>), E> {
+        for _ in 0..self.warmup {
+            black_box(f()?);
+        }
+
+        let mut times = Vec::with_capacity(self.repetitions);
+        let mut output = None;
+        for _ in 0..self.repetitions.max(1) {
+            let (o, time) = f()?;
+            times.push(time as u64);
+            output = Some(black_box(o));
+        }
+
+        Ok((output.unwrap(), times))
+    }
+
+    /// Same as [`Sampling::run`] for operations that have to be awaited
+    pub async fn run_async<T, E, F>(&self, mut f: impl FnMut() -> F) -> Result<(T, Vec<u64>), E>
+    where
+        F: Future<Output = Result<(T, u128), E>>,
+    {
+        for _ in 0..self.warmup {
+            black_box(f().await?);
+        }
+
+        let mut times = Vec::with_capacity(self.repetitions);
+        let mut output = None;
+        for _ in 0..self.repetitions.max(1) {
+            let (o, time) = f().await?;
+            times.push(time as u64);
+            output = Some(black_box(o));
+        }
+
+        Ok((output.unwrap(), times))
+    }
+}
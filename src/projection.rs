@@ -0,0 +1,211 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::recommend::{self, stream_rate, Choice};
+use crate::results;
+
+/// Name used in place of a stream for rows that add up all streams of a device
+pub const ALL_STREAMS: &str = "all";
+
+const SECS_PER_DAY: f64 = 24.0 * 60.0 * 60.0;
+const DAYS_PER_MONTH: f64 = 30.0;
+const BYTES_PER_MB: f64 = 1_000_000.0;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Io error {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Csv error {0}")]
+    Csv(#[from] csv::Error),
+    #[error("Results error {0}")]
+    Results(#[from] results::Error),
+}
+
+/// Size of a fleet and what it pays for data
+#[derive(Debug, Clone, Copy)]
+pub struct Fleet {
+    pub devices: u64,
+    /// price of a megabyte, i.e. 10^6 bytes, sent
+    pub price_per_mb: Option<f64>,
+}
+
+/// Data sent by a fleet shipping a stream with a format, codec and batch size, one
+/// row of the projection file
+#[derive(Debug, Clone, Serialize)]
+pub struct Projection {
+    /// stream name, or [`ALL_STREAMS`] for the sum of all simulated streams
+    pub stream: String,
+    pub batch_size: usize,
+    pub format: String,
+    pub codec: String,
+    pub device_bytes_per_day: f64,
+    /// a month being 30 days
+    pub device_bytes_per_month: f64,
+    pub fleet_bytes_per_day: f64,
+    pub fleet_bytes_per_month: f64,
+    pub fleet_cost_per_month: Option<f64>,
+}
+
+impl Projection {
+    fn new(
+        stream: &str,
+        batch_size: usize,
+        format: &str,
+        codec: &str,
+        bytes_per_sec: f64,
+        fleet: Fleet,
+    ) -> Projection {
+        let device_bytes_per_day = bytes_per_sec * SECS_PER_DAY;
+        let fleet_bytes_per_month = device_bytes_per_day * DAYS_PER_MONTH * fleet.devices as f64;
+
+        Projection {
+            stream: stream.to_owned(),
+            batch_size,
+            format: format.to_owned(),
+            codec: codec.to_owned(),
+            device_bytes_per_day,
+            device_bytes_per_month: device_bytes_per_day * DAYS_PER_MONTH,
+            fleet_bytes_per_day: device_bytes_per_day * fleet.devices as f64,
+            fleet_bytes_per_month,
+            fleet_cost_per_month: fleet
+                .price_per_mb
+                .map(|price| fleet_bytes_per_month / BYTES_PER_MB * price),
+        }
+    }
+}
+
+/// Projects sizes measured per batch onto the rates at which simulated devices
+/// generate points. Per stream rows are followed by rows adding up all streams,
+/// for combinations measured on every stream with a known rate
+pub fn project(choices: &[Choice], fleet: Fleet) -> Vec<Projection> {
+    let mut projections = vec![];
+    let mut totals: BTreeMap<(usize, &str, &str), (usize, f64)> = BTreeMap::new();
+    for choice in choices {
+        let Some(rate) = stream_rate(&choice.stream) else {
+            continue;
+        };
+        let bytes_per_sec = choice.bytes * rate / choice.batch_size as f64;
+        projections.push(Projection::new(
+            &choice.stream,
+            choice.batch_size,
+            &choice.format,
+            &choice.codec,
+            bytes_per_sec,
+            fleet,
+        ));
+
+        let key = (
+            choice.batch_size,
+            choice.format.as_str(),
+            choice.codec.as_str(),
+        );
+        let total = totals.entry(key).or_default();
+        total.0 += 1;
+        total.1 += bytes_per_sec;
+    }
+
+    let mut streams: Vec<&str> = projections.iter().map(|p| p.stream.as_str()).collect();
+    streams.sort_unstable();
+    streams.dedup();
+    let streams = streams.len();
+
+    let mut all: Vec<Projection> = totals
+        .into_iter()
+        .filter(|(_, (count, _))| *count == streams)
+        .map(|((batch_size, format, codec), (_, bytes_per_sec))| {
+            Projection::new(ALL_STREAMS, batch_size, format, codec, bytes_per_sec, fleet)
+        })
+        .collect();
+    all.sort_by(|a, b| a.fleet_bytes_per_month.total_cmp(&b.fleet_bytes_per_month));
+    projections.extend(all);
+
+    projections
+}
+
+/// Writes projections of a results file into `projection.csv` in `output`
+pub fn generate(input: &Path, output: &Path, fleet: Fleet) -> Result<Vec<Projection>, Error> {
+    let choices = recommend::choices(results::read(input)?);
+    let projections = project(&choices, fleet);
+
+    std::fs::create_dir_all(output)?;
+    let mut writer = csv::Writer::from_writer(File::create(output.join("projection.csv"))?);
+    for projection in projections.iter() {
+        writer.serialize(projection)?;
+    }
+    writer.flush()?;
+
+    Ok(projections)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::results::NO_CODEC;
+
+    fn choice(stream: &str, format: &str, bytes: f64) -> Choice {
+        Choice {
+            stream: stream.to_owned(),
+            batch_size: 10,
+            format: format.to_owned(),
+            codec: NO_CODEC.to_owned(),
+            bytes,
+            encode_ns: 0.0,
+        }
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() <= 1e-9 * b.abs()
+    }
+
+    #[test]
+    fn sizes_are_projected_onto_stream_rates() {
+        let fleet = Fleet {
+            devices: 1000,
+            price_per_mb: Some(0.01),
+        };
+        // gps points come once a second and imu points ten times a second
+        let choices = [
+            choice("gps", "a", 100.0),
+            choice("imu", "a", 50.0),
+            // not measured for every stream
+            choice("gps", "b", 10.0),
+            choice("weather", "a", 1e6),
+        ];
+
+        let projections = project(&choices, fleet);
+        let rows: Vec<_> = projections
+            .iter()
+            .map(|p| (p.stream.as_str(), p.format.as_str()))
+            .collect();
+        assert_eq!(
+            rows,
+            [("gps", "a"), ("imu", "a"), ("gps", "b"), (ALL_STREAMS, "a")]
+        );
+
+        // 10 bytes a second
+        assert!(close(projections[0].device_bytes_per_day, 864_000.0));
+        // 50 bytes a second
+        assert!(close(projections[1].device_bytes_per_day, 4_320_000.0));
+        assert!(close(projections[2].device_bytes_per_day, 86_400.0));
+
+        let all = &projections[3];
+        assert!(close(all.device_bytes_per_day, 5_184_000.0));
+        assert!(close(all.device_bytes_per_month, 155_520_000.0));
+        assert!(close(all.fleet_bytes_per_day, 5_184_000_000.0));
+        assert!(close(all.fleet_bytes_per_month, 155_520_000_000.0));
+        assert!(close(all.fleet_cost_per_month.unwrap(), 1555.2));
+    }
+
+    #[test]
+    fn costs_are_left_out_without_a_price() {
+        let fleet = Fleet {
+            devices: 1,
+            price_per_mb: None,
+        };
+        let projections = project(&[choice("gps", "a", 100.0)], fleet);
+        assert!(projections.iter().all(|p| p.fleet_cost_per_month.is_none()));
+    }
+}
@@ -0,0 +1,400 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Display;
+use std::fs::File;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::report::{self, SeriesKey};
+use crate::results::{self, Metric, NO_CODEC};
+use crate::simulator::{next_event_duration, DataEventType};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Io error {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Csv error {0}")]
+    Csv(#[from] csv::Error),
+    #[error("Results error {0}")]
+    Results(#[from] results::Error),
+}
+
+/// Resources a device may spend on shipping data, unlimited if not set
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Budget {
+    /// bytes per second
+    pub bandwidth: Option<f64>,
+    /// percent of a cpu core
+    pub cpu: Option<f64>,
+}
+
+/// Cost of shipping batches of a stream with a format & codec
+#[derive(Debug, Clone, Serialize)]
+pub struct Choice {
+    pub stream: String,
+    pub batch_size: usize,
+    pub format: String,
+    pub codec: String,
+    /// mean size of an encoded batch
+    pub bytes: f64,
+    /// mean nanoseconds taken to serialize and then compress a batch
+    pub encode_ns: f64,
+}
+
+impl Choice {
+    fn dominates(&self, other: &Choice) -> bool {
+        self.bytes <= other.bytes
+            && self.encode_ns <= other.encode_ns
+            && (self.bytes < other.bytes || self.encode_ns < other.encode_ns)
+    }
+}
+
+/// Pareto optimal choices by stream and batch size
+pub type Frontier = BTreeMap<(String, usize), Vec<Choice>>;
+
+/// A format, codec and batch size to be used for all streams, along with why it was picked
+#[derive(Debug, Clone)]
+pub struct Recommendation {
+    pub format: String,
+    pub codec: String,
+    pub batch_size: usize,
+    /// bytes per second a device sends over all streams
+    pub bandwidth: f64,
+    /// percent of a cpu core a device spends on encoding all streams
+    pub cpu: f64,
+    pub reasoning: Vec<String>,
+}
+
+impl Display for Recommendation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Recommendation: {} & {} at batch size {}, sending {:.0} bytes/s and spending {:.3}% of a cpu core on serialization and compression per device",
+            self.format, self.codec, self.batch_size, self.bandwidth, self.cpu
+        )?;
+        for reason in self.reasoning.iter() {
+            writeln!(f, "  - {}", reason)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Combines mean sizes and encoding times of a results file into choices
+pub fn choices(records: Vec<results::BenchRecord>) -> Vec<Choice> {
+    let means: BTreeMap<SeriesKey, f64> = report::group(records)
+        .iter()
+        .map(|(key, values)| (key.clone(), report::summarize(key, values).mean))
+        .collect();
+
+    let mut choices = vec![];
+    for (key, bytes) in means.iter().filter(|(k, _)| k.metric == Metric::Bytes) {
+        let time = |codec: &str, metric: Metric| {
+            let key = SeriesKey {
+                codec: codec.to_owned(),
+                metric,
+                ..key.clone()
+            };
+            means.get(&key).copied()
+        };
+
+        let Some(serialize_ns) = time(NO_CODEC, Metric::SerializeNs) else {
+            continue;
+        };
+        let compress_ns = match key.codec.as_str() {
+            NO_CODEC => 0.0,
+            codec => match time(codec, Metric::CompressNs) {
+                Some(t) => t,
+                None => continue,
+            },
+        };
+
+        choices.push(Choice {
+            stream: key.stream.clone(),
+            batch_size: key.batch_size,
+            format: key.format.clone(),
+            codec: key.codec.clone(),
+            bytes: *bytes,
+            encode_ns: serialize_ns + compress_ns,
+        });
+    }
+    choices.sort_by(|a, b| {
+        (&a.stream, a.batch_size, &a.format, &a.codec).cmp(&(
+            &b.stream,
+            b.batch_size,
+            &b.format,
+            &b.codec,
+        ))
+    });
+
+    choices
+}
+
+/// Choices not beaten on both size and encoding time by another choice of the same
+/// stream and batch size, ordered by size
+pub fn pareto(choices: &[Choice]) -> Frontier {
+    let mut groups: BTreeMap<(String, usize), Vec<&Choice>> = BTreeMap::new();
+    for choice in choices {
+        let key = (choice.stream.clone(), choice.batch_size);
+        groups.entry(key).or_default().push(choice);
+    }
+
+    groups
+        .into_iter()
+        .map(|(key, group)| {
+            let mut frontier: Vec<Choice> = group
+                .iter()
+                .filter(|c| !group.iter().any(|other| other.dominates(c)))
+                .map(|c| (*c).clone())
+                .collect();
+            frontier.sort_by(|a, b| a.bytes.total_cmp(&b.bytes));
+            (key, frontier)
+        })
+        .collect()
+}
+
+/// Points per second a simulated device generates on `stream`
+pub fn stream_rate(stream: &str) -> Option<f64> {
+    let event_type = DataEventType::from_stream(stream)?;
+
+    Some(1.0 / next_event_duration(event_type).as_secs_f64())
+}
+
+/// Picks the format, codec and batch size with the least bandwidth that fits
+/// within `budget` when used for every stream of a simulated device. If none
+/// fits, the one that exceeds the budget by the smallest factor is picked
+pub fn recommend(choices: &[Choice], budget: Budget) -> Option<Recommendation> {
+    let mut reasoning = vec![];
+    let streams: BTreeSet<&str> = choices.iter().map(|c| c.stream.as_str()).collect();
+    let (known, unknown): (Vec<&str>, Vec<&str>) =
+        streams.into_iter().partition(|s| stream_rate(s).is_some());
+    if !unknown.is_empty() {
+        reasoning.push(format!(
+            "left out {}, as the rate at which devices generate them is unknown",
+            unknown.join(", ")
+        ));
+    }
+
+    // bandwidth and cpu of every combination measured for all streams
+    let mut totals: BTreeMap<(usize, &str, &str), (usize, f64, f64)> = BTreeMap::new();
+    for choice in choices {
+        let Some(rate) = stream_rate(&choice.stream) else {
+            continue;
+        };
+        let batches_per_sec = rate / choice.batch_size as f64;
+        let key = (
+            choice.batch_size,
+            choice.format.as_str(),
+            choice.codec.as_str(),
+        );
+        let total = totals.entry(key).or_default();
+        total.0 += 1;
+        total.1 += choice.bytes * batches_per_sec;
+        total.2 += choice.encode_ns * batches_per_sec / 1e9 * 100.0;
+    }
+    let mut candidates: Vec<((usize, &str, &str), f64, f64)> = totals
+        .into_iter()
+        .filter(|(_, (streams, _, _))| *streams == known.len())
+        .map(|(key, (_, bandwidth, cpu))| (key, bandwidth, cpu))
+        .collect();
+    candidates.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.2.total_cmp(&b.2)));
+
+    let overshoot = |bandwidth: f64, cpu: f64| {
+        let bandwidth = budget.bandwidth.map_or(0.0, |b| bandwidth / b);
+        let cpu = budget.cpu.map_or(0.0, |c| cpu / c);
+        bandwidth.max(cpu)
+    };
+    let fits: Vec<_> = candidates
+        .iter()
+        .filter(|(_, bandwidth, cpu)| overshoot(*bandwidth, *cpu) <= 1.0)
+        .collect();
+
+    let budget_text = format!(
+        "{} and {}",
+        budget
+            .bandwidth
+            .map_or("unlimited bandwidth".to_owned(), |b| format!(
+                "{:.0} bytes/s",
+                b
+            )),
+        budget
+            .cpu
+            .map_or("unlimited cpu".to_owned(), |c| format!("{}% cpu", c))
+    );
+    let picked = match fits.first() {
+        Some(picked) => {
+            reasoning.push(format!(
+                "{} of {} combinations measured for {} fit within a budget of {}",
+                fits.len(),
+                candidates.len(),
+                known.join(", "),
+                budget_text
+            ));
+            reasoning.push("it uses the least bandwidth of these".to_owned());
+            if let Some(((batch_size, format, codec), bandwidth, cpu)) = fits.get(1) {
+                reasoning.push(format!(
+                    "runner up is {} & {} at batch size {}, with {:.0} bytes/s and {:.3}% cpu",
+                    format, codec, batch_size, bandwidth, cpu
+                ));
+            }
+            *picked
+        }
+        None => {
+            let picked = candidates
+                .iter()
+                .min_by(|a, b| overshoot(a.1, a.2).total_cmp(&overshoot(b.1, b.2)))?;
+            reasoning.push(format!(
+                "none of {} combinations measured for {} fit within a budget of {}",
+                candidates.len(),
+                known.join(", "),
+                budget_text
+            ));
+            reasoning.push(format!(
+                "it exceeds the budget the least, by a factor of {:.2}",
+                overshoot(picked.1, picked.2)
+            ));
+            picked
+        }
+    };
+
+    let ((batch_size, format, codec), bandwidth, cpu) = *picked;
+    Some(Recommendation {
+        format: format.to_owned(),
+        codec: codec.to_owned(),
+        batch_size,
+        bandwidth,
+        cpu,
+        reasoning,
+    })
+}
+
+/// Writes the pareto frontier of every stream and batch size in a results file
+/// into `pareto.csv` in `output`, and recommends a single choice for `budget`
+pub fn generate(
+    input: &Path,
+    output: &Path,
+    budget: Budget,
+) -> Result<(Frontier, Option<Recommendation>), Error> {
+    let choices = choices(results::read(input)?);
+    let frontier = pareto(&choices);
+
+    std::fs::create_dir_all(output)?;
+    let mut writer = csv::Writer::from_writer(File::create(output.join("pareto.csv"))?);
+    for choice in frontier.values().flatten() {
+        writer.serialize(choice)?;
+    }
+    writer.flush()?;
+
+    Ok((frontier, recommend(&choices, budget)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn choice(stream: &str, format: &str, bytes: f64, encode_ns: f64) -> Choice {
+        Choice {
+            stream: stream.to_owned(),
+            batch_size: 10,
+            format: format.to_owned(),
+            codec: NO_CODEC.to_owned(),
+            bytes,
+            encode_ns,
+        }
+    }
+
+    fn formats(choices: &[Choice]) -> Vec<&str> {
+        choices.iter().map(|c| c.format.as_str()).collect()
+    }
+
+    #[test]
+    fn dominated_choices_are_dropped() {
+        let choices = [
+            choice("imu", "small", 100.0, 900.0),
+            choice("imu", "fast", 300.0, 100.0),
+            choice("imu", "balanced", 200.0, 500.0),
+            // larger and slower than balanced
+            choice("imu", "worse", 250.0, 600.0),
+            // as large as small and slower
+            choice("imu", "slow", 100.0, 950.0),
+            // another stream, which nothing of imu dominates
+            choice("gps", "worse", 1000.0, 1000.0),
+        ];
+
+        let frontier = pareto(&choices);
+        assert_eq!(
+            formats(&frontier[&("imu".to_owned(), 10)]),
+            ["small", "balanced", "fast"]
+        );
+        assert_eq!(formats(&frontier[&("gps".to_owned(), 10)]), ["worse"]);
+    }
+
+    #[test]
+    fn tied_choices_are_both_kept() {
+        let choices = [
+            choice("imu", "a", 100.0, 500.0),
+            choice("imu", "b", 100.0, 500.0),
+            choice("imu", "c", 100.0, 600.0),
+        ];
+
+        let frontier = pareto(&choices);
+        assert_eq!(formats(&frontier[&("imu".to_owned(), 10)]), ["a", "b"]);
+    }
+
+    /// gps points come once a second and imu points ten times a second, so `a`
+    /// sends 110 bytes/s spending 0.11% cpu and `b` 55 bytes/s spending 1.1% cpu
+    fn measured() -> Vec<Choice> {
+        vec![
+            choice("gps", "a", 100.0, 1e6),
+            choice("imu", "a", 100.0, 1e6),
+            choice("gps", "b", 50.0, 1e7),
+            choice("imu", "b", 50.0, 1e7),
+            // not measured for every stream
+            choice("gps", "c", 1.0, 1.0),
+        ]
+    }
+
+    #[test]
+    fn least_bandwidth_within_budget_is_recommended() {
+        let unlimited = recommend(&measured(), Budget::default()).unwrap();
+        assert_eq!(unlimited.format, "b");
+        assert!((unlimited.bandwidth - 55.0).abs() < 1e-9);
+        assert!((unlimited.cpu - 1.1).abs() < 1e-9);
+
+        let cpu = Budget {
+            bandwidth: None,
+            cpu: Some(0.5),
+        };
+        let recommendation = recommend(&measured(), cpu).unwrap();
+        assert_eq!(recommendation.format, "a");
+        assert!((recommendation.bandwidth - 110.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn least_overshoot_is_recommended_if_nothing_fits() {
+        // a exceeds bandwidth 11 fold and b 5.5 fold
+        let bandwidth = Budget {
+            bandwidth: Some(10.0),
+            cpu: None,
+        };
+        assert_eq!(recommend(&measured(), bandwidth).unwrap().format, "b");
+
+        // a exceeds cpu 2.2 fold and b 22 fold
+        let both = Budget {
+            bandwidth: Some(100.0),
+            cpu: Some(0.05),
+        };
+        assert_eq!(recommend(&measured(), both).unwrap().format, "a");
+    }
+
+    #[test]
+    fn streams_of_unknown_rate_are_left_out() {
+        let mut choices = measured();
+        choices.push(choice("weather", "c", 1.0, 1.0));
+
+        let recommendation = recommend(&choices, Budget::default()).unwrap();
+        assert_eq!(recommendation.format, "b");
+        assert!(recommendation.reasoning[0].contains("weather"));
+        assert!(recommend(&[], Budget::default()).is_none());
+    }
+}
@@ -0,0 +1,307 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs::File;
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::results::{self, BenchRecord, Metric};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Io error {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Csv error {0}")]
+    Csv(#[from] csv::Error),
+    #[error("Results error {0}")]
+    Results(#[from] results::Error),
+}
+
+/// Identifies a series of measurements of one metric
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SeriesKey {
+    pub stream: String,
+    pub batch_size: usize,
+    pub format: String,
+    pub codec: String,
+    pub metric: Metric,
+}
+
+/// Descriptive statistics of a series of measurements, one row of an analysis file
+#[derive(Debug, Clone, Serialize)]
+pub struct Summary {
+    pub stream: String,
+    pub batch_size: usize,
+    pub format: String,
+    pub codec: String,
+    pub metric: Metric,
+    pub count: usize,
+    pub total: u64,
+    pub min: u64,
+    pub mean: f64,
+    /// bounds of the 95% confidence interval of the mean
+    pub ci95_low: f64,
+    pub ci95_high: f64,
+    pub median: f64,
+    pub p95: f64,
+    pub p99: f64,
+    pub stddev: f64,
+}
+
+/// Groups records into series of the same stream, batch size, format, codec and metric
+pub fn group(records: Vec<BenchRecord>) -> BTreeMap<SeriesKey, Vec<u64>> {
+    let mut series: BTreeMap<SeriesKey, Vec<u64>> = BTreeMap::new();
+    for record in records {
+        let key = SeriesKey {
+            stream: record.stream,
+            batch_size: record.batch_size,
+            format: record.format,
+            codec: record.codec,
+            metric: record.metric,
+        };
+        series.entry(key).or_default().push(record.value);
+    }
+
+    series
+}
+
+pub fn summarize(key: &SeriesKey, values: &[u64]) -> Summary {
+    let mut sorted = values.to_vec();
+    sorted.sort_unstable();
+
+    let count = sorted.len();
+    let total: u64 = sorted.iter().sum();
+    let mean = total as f64 / count as f64;
+    let variance = match count {
+        0 | 1 => 0.0,
+        _ => {
+            sorted
+                .iter()
+                .map(|v| (*v as f64 - mean).powi(2))
+                .sum::<f64>()
+                / (count - 1) as f64
+        }
+    };
+
+    // normal approximation, good enough for the sample counts of a benchmark run
+    let ci95 = 1.96 * variance.sqrt() / (count as f64).sqrt();
+
+    Summary {
+        stream: key.stream.clone(),
+        batch_size: key.batch_size,
+        format: key.format.clone(),
+        codec: key.codec.clone(),
+        metric: key.metric,
+        count,
+        total,
+        min: sorted.first().copied().unwrap_or_default(),
+        mean,
+        ci95_low: mean - ci95,
+        ci95_high: mean + ci95,
+        median: percentile(&sorted, 50.0),
+        p95: percentile(&sorted, 95.0),
+        p99: percentile(&sorted, 99.0),
+        stddev: variance.sqrt(),
+    }
+}
+
+/// Linearly interpolated percentile of sorted values
+pub fn percentile(sorted: &[u64], p: f64) -> f64 {
+    match sorted.len() {
+        0 => 0.0,
+        1 => sorted[0] as f64,
+        n => {
+            let rank = p / 100.0 * (n - 1) as f64;
+            let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+            let weight = rank - lower as f64;
+
+            sorted[lower] as f64 * (1.0 - weight) + sorted[upper] as f64 * weight
+        }
+    }
+}
+
+/// Summarizes a results file into `analysis_sizes.csv`, `analysis_times.csv`
+/// and a pair of svg charts per batch size and stream in `output`
+pub fn generate(input: &Path, output: &Path) -> Result<(), Error> {
+    let records = results::read(input)?;
+    let summaries: Vec<Summary> = group(records)
+        .iter()
+        .map(|(key, values)| summarize(key, values))
+        .collect();
+
+    std::fs::create_dir_all(output)?;
+    let (sizes, times): (Vec<&Summary>, Vec<&Summary>) = summaries
+        .iter()
+        .partition(|s| matches!(s.metric, Metric::Bytes));
+
+    write_summaries(&output.join("analysis_sizes.csv"), &sizes)?;
+    write_summaries(&output.join("analysis_times.csv"), &times)?;
+
+    let mut charts: BTreeMap<(usize, &str), Vec<&Summary>> = BTreeMap::new();
+    for summary in summaries.iter() {
+        let key = (summary.batch_size, summary.stream.as_str());
+        charts.entry(key).or_default().push(summary);
+    }
+
+    for ((batch_size, stream), summaries) in charts {
+        let (sizes, times): (Vec<&Summary>, Vec<&Summary>) = summaries
+            .into_iter()
+            .partition(|s| matches!(s.metric, Metric::Bytes));
+
+        let title = "Mean byte size from serialization and compression";
+        let svg = bar_chart(title, Metric::Bytes.unit(), &bars(&sizes));
+        std::fs::write(output.join(format!("{batch_size}_{stream}_sizes.svg")), svg)?;
+
+        let title = "Mean time from serialization and compression";
+        let svg = bar_chart(title, Metric::SerializeNs.unit(), &bars(&times));
+        std::fs::write(output.join(format!("{batch_size}_{stream}_times.svg")), svg)?;
+    }
+
+    Ok(())
+}
+
+fn write_summaries(path: &Path, summaries: &[&Summary]) -> Result<(), Error> {
+    let mut writer = csv::Writer::from_writer(File::create(path)?);
+    for summary in summaries {
+        writer.serialize(summary)?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+fn bars(summaries: &[&Summary]) -> Vec<(String, f64)> {
+    summaries
+        .iter()
+        .map(|s| {
+            let label = match s.metric {
+                Metric::Bytes => format!("{} & {}", s.format, s.codec),
+                m => format!("{} & {} {}", s.format, s.codec, m),
+            };
+            (label, s.mean)
+        })
+        .collect()
+}
+
+/// Renders a horizontal bar chart, one bar per labelled value
+fn bar_chart(title: &str, unit: &str, bars: &[(String, f64)]) -> String {
+    const LABEL_WIDTH: f64 = 260.0;
+    const BAR_WIDTH: f64 = 480.0;
+    const ROW_HEIGHT: f64 = 18.0;
+    const TOP: f64 = 40.0;
+
+    let max = bars.iter().map(|(_, v)| *v).fold(0.0, f64::max);
+    let width = LABEL_WIDTH + BAR_WIDTH + 100.0;
+    let height = TOP + ROW_HEIGHT * bars.len() as f64 + 20.0;
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" font-family="sans-serif" font-size="11">"#
+    );
+    let _ = writeln!(
+        svg,
+        r#"<text x="{}" y="20" font-size="14" text-anchor="middle">{} ({})</text>"#,
+        width / 2.0,
+        escape(title),
+        unit
+    );
+
+    for (i, (label, value)) in bars.iter().enumerate() {
+        let y = TOP + ROW_HEIGHT * i as f64;
+        let length = if max > 0.0 {
+            value / max * BAR_WIDTH
+        } else {
+            0.0
+        };
+        let _ = writeln!(
+            svg,
+            r#"<text x="{}" y="{}" text-anchor="end">{}</text>"#,
+            LABEL_WIDTH - 6.0,
+            y + ROW_HEIGHT * 0.7,
+            escape(label)
+        );
+        let _ = writeln!(
+            svg,
+            r##"<rect x="{LABEL_WIDTH}" y="{}" width="{length:.1}" height="{}" fill="#4c72b0"/>"##,
+            y + 2.0,
+            ROW_HEIGHT - 4.0
+        );
+        let _ = writeln!(
+            svg,
+            r#"<text x="{:.1}" y="{}">{value:.1}</text>"#,
+            LABEL_WIDTH + length + 4.0,
+            y + ROW_HEIGHT * 0.7
+        );
+    }
+    svg.push_str("</svg>\n");
+
+    svg
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key() -> SeriesKey {
+        SeriesKey {
+            stream: "imu".to_owned(),
+            batch_size: 10,
+            format: "json".to_owned(),
+            codec: results::NO_CODEC.to_owned(),
+            metric: Metric::SerializeNs,
+        }
+    }
+
+    #[test]
+    fn single_sample() {
+        let summary = summarize(&key(), &[42]);
+
+        assert_eq!(summary.count, 1);
+        assert_eq!(summary.total, 42);
+        assert_eq!(summary.min, 42);
+        assert_eq!(summary.mean, 42.0);
+        assert_eq!(summary.median, 42.0);
+        assert_eq!(summary.p99, 42.0);
+        assert_eq!(summary.stddev, 0.0);
+        assert_eq!((summary.ci95_low, summary.ci95_high), (42.0, 42.0));
+    }
+
+    #[test]
+    fn median_of_even_count_is_between_middle_values() {
+        let summary = summarize(&key(), &[40, 10, 30, 20]);
+
+        assert_eq!(summary.median, 25.0);
+        assert_eq!(summary.min, 10);
+        assert_eq!(summary.mean, 25.0);
+    }
+
+    #[test]
+    fn ci95_of_known_sample() {
+        // mean 5, squared deviations summing to 32 over 7 degrees of freedom
+        let summary = summarize(&key(), &[2, 4, 4, 4, 5, 5, 7, 9]);
+
+        let stddev = (32.0f64 / 7.0).sqrt();
+        let half_width = 1.96 * stddev / 8f64.sqrt();
+        assert_eq!(summary.mean, 5.0);
+        assert!((summary.stddev - stddev).abs() < 1e-12);
+        assert!((summary.ci95_low - (5.0 - half_width)).abs() < 1e-12);
+        assert!((summary.ci95_high - (5.0 + half_width)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn percentiles_interpolate_between_ranks() {
+        let sorted: Vec<u64> = (1..=11).collect();
+
+        assert_eq!(percentile(&sorted, 0.0), 1.0);
+        assert_eq!(percentile(&sorted, 95.0), 10.5);
+        assert_eq!(percentile(&sorted, 100.0), 11.0);
+        assert_eq!(percentile(&[], 50.0), 0.0);
+    }
+}
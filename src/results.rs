@@ -0,0 +1,152 @@
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Io error {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Csv error {0}")]
+    Csv(#[from] csv::Error),
+    #[error("Json error {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// Codec name used in records of uncompressed measurements
+pub const NO_CODEC: &str = "none";
+
+/// Quantities measured for every batch
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Metric {
+    /// nanoseconds taken to serialize a batch
+    SerializeNs,
+    /// nanoseconds taken to deserialize a batch
+    DeserializeNs,
+    /// nanoseconds taken to read a set of fields of every point of a serialized batch
+    ReadNs,
+    /// nanoseconds taken to compress a serialized batch
+    CompressNs,
+    /// nanoseconds taken to decompress a serialized batch
+    DecompressNs,
+    /// length of a serialized batch after compression, if any
+    Bytes,
+}
+
+impl Display for Metric {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::SerializeNs => f.write_str("serialize_ns"),
+            Self::DeserializeNs => f.write_str("deserialize_ns"),
+            Self::ReadNs => f.write_str("read_ns"),
+            Self::CompressNs => f.write_str("compress_ns"),
+            Self::DecompressNs => f.write_str("decompress_ns"),
+            Self::Bytes => f.write_str("bytes"),
+        }
+    }
+}
+
+impl Metric {
+    /// Unit in which values of this metric are recorded
+    pub fn unit(&self) -> &'static str {
+        match self {
+            Self::Bytes => "bytes",
+            _ => "nanos",
+        }
+    }
+}
+
+/// A single measurement, one row of the long-format results
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenchRecord {
+    pub stream: String,
+    pub batch_size: usize,
+    pub format: String,
+    /// compression codec, [`NO_CODEC`] for measurements of the bare format
+    pub codec: String,
+    pub metric: Metric,
+    /// time of a single timed repetition for time metrics
+    pub value: u64,
+}
+
+/// File formats in which benchmark results can be written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ResultFormat {
+    Csv,
+    Jsonl,
+}
+
+impl ResultFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Jsonl => "jsonl",
+        }
+    }
+}
+
+pub enum ResultWriter {
+    Csv(Box<csv::Writer<File>>),
+    Jsonl(BufWriter<File>),
+}
+
+impl ResultWriter {
+    /// Creates `results.<ext>` in `dir`, replacing previous results
+    pub fn create(dir: &Path, format: ResultFormat) -> Result<ResultWriter, Error> {
+        let file = File::create(dir.join(format!("results.{}", format.extension())))?;
+        let writer = match format {
+            ResultFormat::Csv => Self::Csv(Box::new(csv::Writer::from_writer(file))),
+            ResultFormat::Jsonl => Self::Jsonl(BufWriter::new(file)),
+        };
+
+        Ok(writer)
+    }
+
+    pub fn write(&mut self, record: &BenchRecord) -> Result<(), Error> {
+        match self {
+            Self::Csv(writer) => writer.serialize(record)?,
+            Self::Jsonl(writer) => {
+                serde_json::to_writer(&mut *writer, record)?;
+                writer.write_all(b"\n")?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), Error> {
+        match self {
+            Self::Csv(writer) => writer.flush()?,
+            Self::Jsonl(writer) => writer.flush()?,
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads all records from a results file, format is picked by the file's extension
+pub fn read(path: &Path) -> Result<Vec<BenchRecord>, Error> {
+    let file = File::open(path)?;
+    let mut records = vec![];
+
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("jsonl") => {
+            for line in BufReader::new(file).lines() {
+                let line = line?;
+                if !line.trim().is_empty() {
+                    records.push(serde_json::from_str(&line)?);
+                }
+            }
+        }
+        _ => {
+            for record in csv::Reader::from_reader(file).deserialize() {
+                records.push(record?);
+            }
+        }
+    }
+
+    Ok(records)
+}
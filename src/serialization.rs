@@ -1,27 +1,38 @@
+use std::collections::HashMap;
 use std::fmt::Display;
-use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
 use std::time::Instant;
 
-use flexbuffers::{FlexbufferSerializer, Reader};
-// use apache_avro::{from_value, to_value, Reader, Schema, Writer};
-use prost_reflect::{prost::Message, DescriptorPool, DynamicMessage, SerializeOptions};
+use flexbuffers::{FlexBufferType, FlexbufferSerializer, Reader};
+use prost_reflect::{prost::Message, DescriptorPool, DynamicMessage, MessageDescriptor};
 use serde::{Deserialize, Serialize};
-use serde_json::{Deserializer, Serializer};
 use serde_pickle::{DeOptions, SerOptions};
 
+mod arrow_format;
+mod avro;
 mod capnproto;
+mod columnar;
+mod flatbuffers_format;
+mod framing;
+mod native;
 mod proto;
 
+pub use capnproto::{read_points, write_points};
+pub use framing::{Frame, FrameReader, FrameWriter};
+
 use crate::Payload;
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("Io error {0}")]
     Io(#[from] std::io::Error),
-    // #[error("Avro serialization error {0}")]
-    // Avro(#[from] apache_avro::Error),
-    // #[error("Avro serialization missing element")]
-    // AvroMissing,
+    #[error("Avro serialization error {0}")]
+    Avro(#[from] apache_avro::Error),
+    #[error("Avro serialization missing element")]
+    AvroMissing,
+    #[error("Bincode error {0}")]
+    Bincode(#[from] bincode::Error),
     #[error("Bson serialization error {0}")]
     BsonSer(#[from] bson::ser::Error),
     #[error("Bson deserialization error {0}")]
@@ -34,6 +45,10 @@ pub enum Error {
     Json(#[from] serde_json::Error),
     #[error("Capn error: {0}")]
     Capn(#[from] capnp::Error),
+    #[error("Capn text error: {0}")]
+    CapnText(#[from] std::str::Utf8Error),
+    #[error("Flatbuffers verification error {0}")]
+    FlatBuffers(#[from] flatbuffers::InvalidFlatbuffer),
     #[error("Flexbuffers serialization error {0}")]
     FBSer(#[from] flexbuffers::SerializationError),
     #[error("Flexbuffers deserialization error {0}")]
@@ -42,6 +57,10 @@ pub enum Error {
     FBReader(#[from] flexbuffers::ReaderError),
     #[error("Pickle error: {0}")]
     Pickle(#[from] serde_pickle::Error),
+    #[error("Postcard error: {0}")]
+    Postcard(#[from] postcard::Error),
+    #[error("Rkyv error: {0}")]
+    Rkyv(#[from] rkyv::rancor::Error),
     #[error("RMP Encode error: {0}")]
     RmpEncode(#[from] rmp_serde::encode::Error),
     #[error("RMP Decode error: {0}")]
@@ -52,6 +71,40 @@ pub enum Error {
     ProstEncode(#[from] prost_reflect::prost::EncodeError),
     #[error("Prost reflect decode error = {0}")]
     ProstDecode(#[from] prost_reflect::prost::DecodeError),
+    #[error("No schema for stream {0}")]
+    UnknownStream(String),
+    #[error("Stream {stream} has list messages {first} and {second}")]
+    AmbiguousStream {
+        stream: String,
+        first: String,
+        second: String,
+    },
+    #[error("Schema message {0} missing or not a list of points")]
+    UnknownMessage(String),
+    #[error("Point of stream {stream} is missing field {field}")]
+    MissingField { stream: String, field: String },
+    #[error("Field {field} of stream {stream} is {found}, expected {expected}")]
+    TypeMismatch {
+        stream: String,
+        field: String,
+        expected: String,
+        found: String,
+    },
+    #[error("Protoc failed: {0}")]
+    Protoc(String),
+    #[error("Arrow error: {0}")]
+    Arrow(#[from] arrow::error::ArrowError),
+    #[error("Parquet error: {0}")]
+    Parquet(#[from] parquet::errors::ParquetError),
+    #[error("Columnar batch malformed: {0}")]
+    Columnar(&'static str),
+    #[error("Frame malformed: {0}")]
+    Frame(&'static str),
+    #[error("Unknown format {0}")]
+    UnknownFormat(String),
+    /// errors of formats registered from outside zerde
+    #[error("{0}")]
+    Other(Box<dyn std::error::Error + Send + Sync>),
 }
 
 #[derive(Debug, Serialize, Deserialize, Default, Clone)]
@@ -59,401 +112,958 @@ struct PayloadArray {
     messages: Vec<Payload>,
 }
 
-#[derive(Debug, Clone)]
-pub enum Algo<'a> {
-    Bson,
-    Capn(&'a str),
-    Cbor,
-    FlexBuffers,
-    Json,
-    MessagePack,
-    Pickle,
-    Proto(&'a str),
-    ProtoReflect(&'a DescriptorPool, &'a str),
-}
-
-impl Display for Algo<'_> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+/// How the values a field holds across a batch are folded into one number
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fold {
+    Sum,
+    Max,
+}
+
+impl Fold {
+    /// Fold of a batch without the field
+    pub fn empty(self) -> f64 {
         match self {
-            Self::ProtoReflect(_, s) => f.write_fmt(format_args!("ProtoBuf: {}", s)),
-            a => f.write_fmt(format_args!("{:?}", a)),
+            Fold::Sum => 0.0,
+            Fold::Max => f64::NEG_INFINITY,
+        }
+    }
+
+    pub fn step(self, folded: f64, value: f64) -> f64 {
+        match self {
+            Fold::Sum => folded + value,
+            Fold::Max => folded.max(value),
         }
     }
 }
 
-impl<'a> Algo<'a> {
-    pub fn serialize(&self, payload: Vec<Payload>) -> Result<(Vec<u8>, u128), Error> {
-        let now = Instant::now();
-        let serialized = match self {
-            // Self::Avro(schema) => self.avro_serialize(payload, schema),
-            Self::Bson => self.bson_serialize(payload)?,
-            Self::Capn(stream) => capnproto::serialize(payload, stream)?,
-            Self::Cbor => self.cbor_serialize(payload)?,
-            Self::FlexBuffers => self.flexbuffers_serialize(payload)?,
-            Self::Json => self.json_serialize(payload)?,
-            Self::MessagePack => self.msgpck_serialize(payload)?,
-            Self::Pickle => self.pickle_serialize(payload)?,
-            Self::Proto(stream) => self.proto_serialize(payload, stream)?,
-            Self::ProtoReflect(descriptor_pool, stream) => {
-                self.proto_reflect_serialize(descriptor_pool, payload, stream)?
-            }
-        };
-        let serialization_time = now.elapsed().as_micros();
-
-        Ok((serialized, serialization_time))
-    }
-
-    pub fn deserialize(&self, payload: &[u8]) -> Result<(Vec<Payload>, u128), Error> {
-        let now = Instant::now();
-        let deserialized = match self {
-            // Self::Avro(schema) => self.avro_deserialize(payload, schema),
-            Self::Bson => self.bson_deserialize(payload)?,
-            Self::Capn(stream) => capnproto::deserialize(payload, stream)?,
-            Self::Cbor => self.cbor_deserialize(payload)?,
-            Self::FlexBuffers => self.flexbuffers_deserialize(payload)?,
-            Self::Json => self.json_deserialize(payload)?,
-            Self::MessagePack => self.msgpck_deserialize(payload)?,
-            Self::Pickle => self.pickle_deserialize(payload)?,
-            Self::Proto(stream) => self.proto_deserialize(payload, stream)?,
-            Self::ProtoReflect(descriptor_pool, stream) => {
-                self.proto_reflect_deserialize(descriptor_pool, payload, stream)?
-            }
-        };
-        let deserialization_time = now.elapsed().as_micros();
+/// A numeric field to be read from every point of a serialized batch, written as
+/// `<field>:<fold>` e.g. `pack_voltage:sum` or `timestamp:max`, summed if no fold is given
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldRead {
+    pub field: String,
+    pub fold: Fold,
+}
 
-        Ok((deserialized, deserialization_time))
+impl FieldRead {
+    pub fn fold(&self, values: impl Iterator<Item = f64>) -> f64 {
+        values.fold(self.fold.empty(), |folded, v| self.fold.step(folded, v))
     }
 
-    // fn avro_serialize(&self, payload: Vec<Payload>, schema: &Schema) -> Result<Vec<u8>, Error> {
-    //     let mut serialized = vec![];
-    //     let mut writer = Writer::new(schema, &mut serialized);
-    //     let value = to_value(payload)?;
-    //     writer.append(value)?;
-    //     writer.flush()?;
+    /// Whether points of a batch hold the field as a number
+    pub fn applies(&self, payload: &[Payload]) -> bool {
+        payload
+            .first()
+            .is_some_and(|p| number(p, &self.field).is_some())
+    }
+}
 
-    //     Ok(serialized)
-    // }
+impl FromStr for FieldRead {
+    type Err = String;
 
-    fn bson_serialize(&self, payload: Vec<Payload>) -> Result<Vec<u8>, Error> {
-        let array = PayloadArray {
-            messages: payload.to_owned(),
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (field, fold) = s.split_once(':').unwrap_or((s, "sum"));
+        let fold = match fold {
+            "sum" => Fold::Sum,
+            "max" => Fold::Max,
+            fold => return Err(format!("unknown fold {}, expected sum or max", fold)),
         };
+
+        Ok(FieldRead {
+            field: field.to_owned(),
+            fold,
+        })
+    }
+}
+
+/// A serialization format that batches of points can be measured with
+pub trait Serializer: Send + Sync {
+    /// Name under which the format is selected and reported
+    fn name(&self) -> &str;
+
+    /// Streams the format has a schema for, `None` if it is self describing and
+    /// serializes points of any stream
+    fn schema_streams(&self) -> Option<Vec<String>> {
+        None
+    }
+
+    fn serialize(&self, stream: &str, payload: Vec<Payload>) -> Result<Vec<u8>, Error>;
+
+    fn deserialize(&self, stream: &str, payload: &[u8]) -> Result<Vec<Payload>, Error>;
+
+    /// Folds fields of every point of a serialized batch into a number each. Formats
+    /// that can read fields in place override this, others decode the whole batch
+    fn read_fields(
+        &self,
+        stream: &str,
+        payload: &[u8],
+        reads: &[FieldRead],
+    ) -> Result<Vec<f64>, Error> {
+        let points = self.deserialize(stream, payload)?;
+        let folded = reads
+            .iter()
+            .map(|r| r.fold(points.iter().filter_map(|p| number(p, &r.field))))
+            .collect();
+
+        Ok(folded)
+    }
+
+    /// Whether points of `stream` can be serialized by this format
+    fn supports(&self, stream: &str) -> bool {
+        self.schema_streams()
+            .is_none_or(|streams| streams.iter().any(|s| s == stream))
+    }
+}
+
+/// Serializers that a benchmark run can pick from, in the order they are measured
+#[derive(Default)]
+pub struct Registry {
+    serializers: Vec<Box<dyn Serializer>>,
+}
+
+impl Registry {
+    pub fn new() -> Registry {
+        Registry::default()
+    }
+
+    /// Registry with every format that comes with zerde, fails if a schema built into
+    /// zerde can't be parsed
+    pub fn builtin() -> Result<Registry, Error> {
+        let mut registry = Registry::new();
+        registry.register(Json);
+        registry.register(Proto);
+        registry.register(ProtoReflect::new(proto::descriptor_pool())?);
+        registry.register(MessagePack);
+        registry.register(Bson);
+        registry.register(Cbor);
+        registry.register(Pickle);
+        registry.register(Capn);
+        registry.register(CapnPacked);
+        registry.register(FlatBuffers);
+        registry.register(Avro::new()?);
+        registry.register(AvroContainer::new()?);
+        registry.register(FlexBuffers);
+        registry.register(Bincode);
+        registry.register(Postcard);
+        registry.register(Rkyv);
+        registry.register(Columnar);
+        registry.register(ArrowIpc);
+        registry.register(Parquet);
+
+        Ok(registry)
+    }
+
+    /// Adds a format, replacing a previously registered format of the same name
+    pub fn register(&mut self, serializer: impl Serializer + 'static) {
+        let serializer: Box<dyn Serializer> = Box::new(serializer);
+        match self
+            .serializers
+            .iter_mut()
+            .find(|s| s.name() == serializer.name())
+        {
+            Some(existing) => *existing = serializer,
+            None => self.serializers.push(serializer),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn Serializer> {
+        self.serializers
+            .iter()
+            .find(|s| s.name() == name)
+            .map(|s| s.as_ref())
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.serializers.iter().map(|s| s.name()).collect()
+    }
+
+    /// Serializers of the given names, every registered serializer if `names` is empty
+    pub fn select(&self, names: &[String]) -> Result<Vec<&dyn Serializer>, Error> {
+        if names.is_empty() {
+            return Ok(self.serializers.iter().map(|s| s.as_ref()).collect());
+        }
+
+        names
+            .iter()
+            .map(|name| {
+                self.get(name)
+                    .ok_or_else(|| Error::UnknownFormat(name.to_owned()))
+            })
+            .collect()
+    }
+}
+
+/// Runs `f`, returning its output along with the nanoseconds it took
+pub fn timed<T>(f: impl FnOnce() -> Result<T, Error>) -> Result<(T, u128), Error> {
+    let now = Instant::now();
+    let output = f()?;
+
+    Ok((output, now.elapsed().as_nanos()))
+}
+
+/// Value of a numeric field of a point
+fn number(point: &Payload, field: &str) -> Option<f64> {
+    match field {
+        "sequence" => Some(point.sequence as f64),
+        "timestamp" => Some(point.timestamp as f64),
+        field => point.payload.get(field)?.as_f64(),
+    }
+}
+
+/// Sets the sequence, timestamp or payload key of a point read from the field of a
+/// schema, the sequence and timestamp having to fit theirs
+fn set_point_field(
+    stream: &str,
+    point: &mut Payload,
+    payload: &mut serde_json::Map<String, serde_json::Value>,
+    field: &str,
+    value: serde_json::Value,
+) -> Result<(), Error> {
+    match field {
+        "sequence" => match value.as_u64().and_then(|v| u32::try_from(v).ok()) {
+            Some(sequence) => point.sequence = sequence,
+            None => return Err(type_mismatch(stream, field, "a u32", value)),
+        },
+        "timestamp" => match value.as_u64() {
+            Some(timestamp) => point.timestamp = timestamp,
+            None => return Err(type_mismatch(stream, field, "a u64", value)),
+        },
+        field => {
+            payload.insert(field.to_owned(), value);
+        }
+    }
+
+    Ok(())
+}
+
+/// Error of a field that doesn't hold the type its schema expects
+fn type_mismatch(stream: &str, field: &str, expected: impl Display, found: impl Display) -> Error {
+    Error::TypeMismatch {
+        stream: stream.to_owned(),
+        field: field.to_owned(),
+        expected: expected.to_string(),
+        found: found.to_string(),
+    }
+}
+
+/// Name of the JSON type of a value, for errors on points not matching a schema
+fn json_type(value: &serde_json::Value) -> &'static str {
+    match value {
+        serde_json::Value::Null => "null",
+        serde_json::Value::Bool(_) => "a bool",
+        serde_json::Value::Number(_) => "a number",
+        serde_json::Value::String(_) => "a string",
+        serde_json::Value::Array(_) => "an array",
+        serde_json::Value::Object(_) => "an object",
+    }
+}
+
+/// Batches as a single Arrow record batch, in the IPC stream format
+pub struct ArrowIpc;
+
+impl Serializer for ArrowIpc {
+    fn name(&self) -> &str {
+        "arrow"
+    }
+
+    fn serialize(&self, _: &str, payload: Vec<Payload>) -> Result<Vec<u8>, Error> {
+        arrow_format::serialize_ipc(payload)
+    }
+
+    fn deserialize(&self, stream: &str, payload: &[u8]) -> Result<Vec<Payload>, Error> {
+        arrow_format::deserialize_ipc(payload, stream)
+    }
+
+    fn read_fields(&self, _: &str, payload: &[u8], reads: &[FieldRead]) -> Result<Vec<f64>, Error> {
+        arrow_format::read_fields_ipc(payload, reads)
+    }
+}
+
+/// Avro, a batch being encoded as a single datum holding an array of records
+pub struct Avro {
+    schemas: HashMap<String, avro::StreamSchema>,
+}
+
+impl Avro {
+    pub fn new() -> Result<Avro, Error> {
+        Ok(Avro {
+            schemas: avro::schemas()?,
+        })
+    }
+}
+
+impl Serializer for Avro {
+    fn name(&self) -> &str {
+        "avro"
+    }
+
+    fn schema_streams(&self) -> Option<Vec<String>> {
+        Some(self.schemas.keys().cloned().collect())
+    }
+
+    fn serialize(&self, stream: &str, payload: Vec<Payload>) -> Result<Vec<u8>, Error> {
+        avro::serialize_datum(payload, avro_schema(&self.schemas, stream)?)
+    }
+
+    fn deserialize(&self, stream: &str, payload: &[u8]) -> Result<Vec<Payload>, Error> {
+        avro::deserialize_datum(payload, avro_schema(&self.schemas, stream)?)
+    }
+}
+
+/// Avro object container file, holding the schema and a record per point
+pub struct AvroContainer {
+    schemas: HashMap<String, avro::StreamSchema>,
+}
+
+impl AvroContainer {
+    pub fn new() -> Result<AvroContainer, Error> {
+        Ok(AvroContainer {
+            schemas: avro::schemas()?,
+        })
+    }
+}
+
+impl Serializer for AvroContainer {
+    fn name(&self) -> &str {
+        "avro-container"
+    }
+
+    fn schema_streams(&self) -> Option<Vec<String>> {
+        Some(self.schemas.keys().cloned().collect())
+    }
+
+    fn serialize(&self, stream: &str, payload: Vec<Payload>) -> Result<Vec<u8>, Error> {
+        avro::serialize_container(payload, avro_schema(&self.schemas, stream)?)
+    }
+
+    fn deserialize(&self, stream: &str, payload: &[u8]) -> Result<Vec<Payload>, Error> {
+        avro::deserialize_container(payload, avro_schema(&self.schemas, stream)?)
+    }
+}
+
+fn avro_schema<'a>(
+    schemas: &'a HashMap<String, avro::StreamSchema>,
+    stream: &str,
+) -> Result<&'a avro::StreamSchema, Error> {
+    schemas
+        .get(stream)
+        .ok_or_else(|| Error::UnknownStream(stream.to_owned()))
+}
+
+/// Typed points of a stream with fixed width integers, see the `native` module
+pub struct Bincode;
+
+impl Serializer for Bincode {
+    fn name(&self) -> &str {
+        "bincode"
+    }
+
+    fn schema_streams(&self) -> Option<Vec<String>> {
+        Some(native::STREAMS.iter().map(|s| s.to_string()).collect())
+    }
+
+    fn serialize(&self, stream: &str, payload: Vec<Payload>) -> Result<Vec<u8>, Error> {
+        native::serialize_bincode(payload, stream)
+    }
+
+    fn deserialize(&self, stream: &str, payload: &[u8]) -> Result<Vec<Payload>, Error> {
+        native::deserialize_bincode(payload, stream)
+    }
+}
+
+pub struct Bson;
+
+impl Serializer for Bson {
+    fn name(&self) -> &str {
+        "bson"
+    }
+
+    fn serialize(&self, _: &str, payload: Vec<Payload>) -> Result<Vec<u8>, Error> {
+        let array = PayloadArray { messages: payload };
         let serialized = bson::to_vec(&array)?;
 
         Ok(serialized)
     }
 
-    fn cbor_serialize(&self, payload: Vec<Payload>) -> Result<Vec<u8>, Error> {
-        let mut serialized = vec![];
-        ciborium::ser::into_writer(&payload, &mut serialized)?;
+    fn deserialize(&self, _: &str, payload: &[u8]) -> Result<Vec<Payload>, Error> {
+        let deserialized: PayloadArray = bson::from_slice(payload)?;
 
-        Ok(serialized)
+        Ok(deserialized.messages)
     }
+}
 
-    fn flexbuffers_serialize(&self, payload: Vec<Payload>) -> Result<Vec<u8>, Error> {
-        let mut serialized = FlexbufferSerializer::new();
-        payload.serialize(&mut serialized)?;
+pub struct Capn;
 
-        Ok(serialized.view().to_vec())
+impl Serializer for Capn {
+    fn name(&self) -> &str {
+        "capnproto"
     }
 
-    fn json_serialize(&self, payload: Vec<Payload>) -> Result<Vec<u8>, Error> {
-        let serialized = serde_json::to_vec(&payload)?;
+    fn schema_streams(&self) -> Option<Vec<String>> {
+        Some(capnproto::STREAMS.iter().map(|s| s.to_string()).collect())
+    }
 
-        Ok(serialized)
+    fn serialize(&self, stream: &str, payload: Vec<Payload>) -> Result<Vec<u8>, Error> {
+        capnproto::serialize(payload, stream)
     }
 
-    fn msgpck_serialize(&self, payload: Vec<Payload>) -> Result<Vec<u8>, Error> {
-        let serialized = rmp_serde::to_vec(&payload)?;
+    fn deserialize(&self, stream: &str, payload: &[u8]) -> Result<Vec<Payload>, Error> {
+        capnproto::deserialize(payload, stream)
+    }
 
-        Ok(serialized)
+    fn read_fields(
+        &self,
+        stream: &str,
+        payload: &[u8],
+        reads: &[FieldRead],
+    ) -> Result<Vec<f64>, Error> {
+        capnproto::read_fields(payload, stream, reads, false)
     }
+}
 
-    fn pickle_serialize(&self, payload: Vec<Payload>) -> Result<Vec<u8>, Error> {
-        let serialized = serde_pickle::to_vec(&payload, SerOptions::new())?;
+/// Cap'n Proto with the packed encoding, which shrinks the zeroes padding its words
+pub struct CapnPacked;
 
-        Ok(serialized)
+impl Serializer for CapnPacked {
+    fn name(&self) -> &str {
+        "capnproto-packed"
     }
 
-    fn proto_serialize(&self, payload: Vec<Payload>, stream: &str) -> Result<Vec<u8>, Error> {
-        proto::serialize(payload, stream)
+    fn schema_streams(&self) -> Option<Vec<String>> {
+        Some(capnproto::STREAMS.iter().map(|s| s.to_string()).collect())
+    }
+
+    fn serialize(&self, stream: &str, payload: Vec<Payload>) -> Result<Vec<u8>, Error> {
+        capnproto::serialize_packed(payload, stream)
+    }
+
+    fn deserialize(&self, stream: &str, payload: &[u8]) -> Result<Vec<Payload>, Error> {
+        capnproto::deserialize_packed(payload, stream)
     }
 
-    fn proto_reflect_serialize(
+    fn read_fields(
         &self,
-        descriptor_pool: &DescriptorPool,
-        payload: Vec<Payload>,
         stream: &str,
-    ) -> Result<Vec<u8>, Error> {
-        let desc = descriptor_pool.get_message_by_name(stream).unwrap();
-        let payload = PayloadArray {
-            messages: payload.to_owned(),
-        };
-        let json_serialized = serde_json::to_vec(&payload)?;
+        payload: &[u8],
+        reads: &[FieldRead],
+    ) -> Result<Vec<f64>, Error> {
+        capnproto::read_fields(payload, stream, reads, true)
+    }
+}
 
-        let mut deserializer = Deserializer::from_slice(&json_serialized);
-        let msg = DynamicMessage::deserialize(desc, &mut deserializer)?;
+pub struct Cbor;
 
+impl Serializer for Cbor {
+    fn name(&self) -> &str {
+        "cbor"
+    }
+
+    fn serialize(&self, _: &str, payload: Vec<Payload>) -> Result<Vec<u8>, Error> {
         let mut serialized = vec![];
-        msg.encode(&mut serialized)?;
+        ciborium::ser::into_writer(&payload, &mut serialized)?;
 
         Ok(serialized)
     }
 
-    // fn avro_deserialize(&self, payload: &[u8], schema: &Schema) -> Result<Vec<Payload>, Error> {
-    //     let mut reader = Reader::with_schema(schema, payload)?;
-    //     let value = reader.next().ok_or(Error::AvroMissing)??;
-    //     let deserialized = from_value(&value)?;
+    fn deserialize(&self, _: &str, payload: &[u8]) -> Result<Vec<Payload>, Error> {
+        let deserialized = ciborium::de::from_reader(payload)?;
 
-    //     Ok(deserialized)
-    // }
+        Ok(deserialized)
+    }
+}
 
-    fn bson_deserialize(&self, payload: &[u8]) -> Result<Vec<Payload>, Error> {
-        let deserialized: PayloadArray = bson::from_slice(payload)?;
+/// Batches transposed into a column per field, see the `columnar` module
+pub struct Columnar;
 
-        Ok(deserialized.messages)
+impl Serializer for Columnar {
+    fn name(&self) -> &str {
+        "columnar"
     }
 
-    fn cbor_deserialize(&self, payload: &[u8]) -> Result<Vec<Payload>, Error> {
-        let deserialized = ciborium::de::from_reader(payload)?;
+    fn serialize(&self, _: &str, payload: Vec<Payload>) -> Result<Vec<u8>, Error> {
+        columnar::serialize(payload)
+    }
 
-        Ok(deserialized)
+    fn deserialize(&self, _: &str, payload: &[u8]) -> Result<Vec<Payload>, Error> {
+        columnar::deserialize(payload)
+    }
+}
+
+/// Batches as a list table of the stream's schema in `test.fbs`, read in place
+pub struct FlatBuffers;
+
+impl Serializer for FlatBuffers {
+    fn name(&self) -> &str {
+        "flatbuffers"
+    }
+
+    fn schema_streams(&self) -> Option<Vec<String>> {
+        Some(
+            flatbuffers_format::STREAMS
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        )
+    }
+
+    fn serialize(&self, stream: &str, payload: Vec<Payload>) -> Result<Vec<u8>, Error> {
+        flatbuffers_format::serialize(payload, stream)
+    }
+
+    fn deserialize(&self, stream: &str, payload: &[u8]) -> Result<Vec<Payload>, Error> {
+        flatbuffers_format::deserialize(payload, stream)
+    }
+
+    fn read_fields(
+        &self,
+        stream: &str,
+        payload: &[u8],
+        reads: &[FieldRead],
+    ) -> Result<Vec<f64>, Error> {
+        flatbuffers_format::read_fields(payload, stream, reads)
+    }
+}
+
+pub struct FlexBuffers;
+
+impl Serializer for FlexBuffers {
+    fn name(&self) -> &str {
+        "flexbuffers"
+    }
+
+    fn serialize(&self, _: &str, payload: Vec<Payload>) -> Result<Vec<u8>, Error> {
+        let mut serialized = FlexbufferSerializer::new();
+        payload.serialize(&mut serialized)?;
+
+        Ok(serialized.view().to_vec())
     }
 
-    fn flexbuffers_deserialize(&self, payload: &[u8]) -> Result<Vec<Payload>, Error> {
+    fn deserialize(&self, _: &str, payload: &[u8]) -> Result<Vec<Payload>, Error> {
         let root = Reader::get_root(payload)?;
         let deserialized = Deserialize::deserialize(root)?;
 
         Ok(deserialized)
     }
 
-    fn json_deserialize(&self, payload: &[u8]) -> Result<Vec<Payload>, Error> {
+    fn read_fields(&self, _: &str, payload: &[u8], reads: &[FieldRead]) -> Result<Vec<f64>, Error> {
+        let mut folded: Vec<f64> = reads.iter().map(|r| r.fold.empty()).collect();
+        for point in Reader::get_root(payload)?.get_vector()?.iter() {
+            let point = point.get_map()?;
+            for (read, folded) in reads.iter().zip(folded.iter_mut()) {
+                let Ok(value) = point.index(read.field.as_str()) else {
+                    continue;
+                };
+                let value = match value.flexbuffer_type() {
+                    FlexBufferType::Int => value.get_i64()? as f64,
+                    FlexBufferType::UInt => value.get_u64()? as f64,
+                    FlexBufferType::Float => value.get_f64()?,
+                    _ => continue,
+                };
+                *folded = read.fold.step(*folded, value);
+            }
+        }
+
+        Ok(folded)
+    }
+}
+
+pub struct Json;
+
+impl Serializer for Json {
+    fn name(&self) -> &str {
+        "json"
+    }
+
+    fn serialize(&self, _: &str, payload: Vec<Payload>) -> Result<Vec<u8>, Error> {
+        let serialized = serde_json::to_vec(&payload)?;
+
+        Ok(serialized)
+    }
+
+    fn deserialize(&self, _: &str, payload: &[u8]) -> Result<Vec<Payload>, Error> {
         let deserialized = serde_json::from_slice(payload)?;
 
         Ok(deserialized)
     }
+}
+
+pub struct MessagePack;
+
+impl Serializer for MessagePack {
+    fn name(&self) -> &str {
+        "msgpack"
+    }
+
+    fn serialize(&self, _: &str, payload: Vec<Payload>) -> Result<Vec<u8>, Error> {
+        let serialized = rmp_serde::to_vec(&payload)?;
+
+        Ok(serialized)
+    }
 
-    fn msgpck_deserialize(&self, payload: &[u8]) -> Result<Vec<Payload>, Error> {
+    fn deserialize(&self, _: &str, payload: &[u8]) -> Result<Vec<Payload>, Error> {
         let deserialized = rmp_serde::from_slice(payload)?;
 
         Ok(deserialized)
     }
+}
+
+/// Batches as a Parquet file, through the same record batch as `ArrowIpc`
+pub struct Parquet;
+
+impl Serializer for Parquet {
+    fn name(&self) -> &str {
+        "parquet"
+    }
+
+    fn serialize(&self, _: &str, payload: Vec<Payload>) -> Result<Vec<u8>, Error> {
+        arrow_format::serialize_parquet(payload)
+    }
+
+    fn deserialize(&self, stream: &str, payload: &[u8]) -> Result<Vec<Payload>, Error> {
+        arrow_format::deserialize_parquet(payload, stream)
+    }
+
+    fn read_fields(&self, _: &str, payload: &[u8], reads: &[FieldRead]) -> Result<Vec<f64>, Error> {
+        arrow_format::read_fields_parquet(payload, reads)
+    }
+}
+
+pub struct Pickle;
+
+impl Serializer for Pickle {
+    fn name(&self) -> &str {
+        "pickle"
+    }
+
+    fn serialize(&self, _: &str, payload: Vec<Payload>) -> Result<Vec<u8>, Error> {
+        let serialized = serde_pickle::to_vec(&payload, SerOptions::new())?;
+
+        Ok(serialized)
+    }
 
-    fn pickle_deserialize(&self, payload: &[u8]) -> Result<Vec<Payload>, Error> {
+    fn deserialize(&self, _: &str, payload: &[u8]) -> Result<Vec<Payload>, Error> {
         let deserialized = serde_pickle::from_slice(payload, DeOptions::new())?;
 
         Ok(deserialized)
     }
+}
+
+/// Typed points of a stream with varint integers
+pub struct Postcard;
+
+impl Serializer for Postcard {
+    fn name(&self) -> &str {
+        "postcard"
+    }
 
-    fn proto_deserialize(&self, payload: &[u8], stream: &str) -> Result<Vec<Payload>, Error> {
+    fn schema_streams(&self) -> Option<Vec<String>> {
+        Some(native::STREAMS.iter().map(|s| s.to_string()).collect())
+    }
+
+    fn serialize(&self, stream: &str, payload: Vec<Payload>) -> Result<Vec<u8>, Error> {
+        native::serialize_postcard(payload, stream)
+    }
+
+    fn deserialize(&self, stream: &str, payload: &[u8]) -> Result<Vec<Payload>, Error> {
+        native::deserialize_postcard(payload, stream)
+    }
+}
+
+/// Protobuf, batches being encoded as the list messages prost generated from
+/// `test.proto`
+pub struct Proto;
+
+impl Serializer for Proto {
+    fn name(&self) -> &str {
+        "protobuf"
+    }
+
+    fn schema_streams(&self) -> Option<Vec<String>> {
+        let lists = proto::list_messages(proto::descriptor_pool())
+            .expect("build.rs rejects streams with several list messages");
+        Some(lists.into_keys().collect())
+    }
+
+    fn serialize(&self, stream: &str, payload: Vec<Payload>) -> Result<Vec<u8>, Error> {
+        proto::serialize(payload, stream)
+    }
+
+    fn deserialize(&self, stream: &str, payload: &[u8]) -> Result<Vec<Payload>, Error> {
         proto::deserialize(payload, stream)
     }
+}
+
+/// Where the descriptors of [`ProtoReflect`] are loaded from
+#[derive(Debug, Clone)]
+pub enum ProtoSchema {
+    /// `test.proto`, compiled into zerde by build.rs
+    Embedded,
+    /// a `.proto` file compiled at runtime by protoc, which is looked up through the
+    /// `PROTOC` env var or on the path. Imports are resolved against `includes`, or
+    /// the directory of the file if none are given
+    Proto {
+        path: PathBuf,
+        includes: Vec<PathBuf>,
+    },
+    /// a `FileDescriptorSet`, as written by `protoc --descriptor_set_out`
+    DescriptorSet(PathBuf),
+}
+
+impl ProtoSchema {
+    pub fn load(&self) -> Result<DescriptorPool, Error> {
+        match self {
+            ProtoSchema::Embedded => Ok(proto::descriptor_pool().clone()),
+            ProtoSchema::Proto { path, includes } => proto::compile(path, includes),
+            ProtoSchema::DescriptorSet(path) => {
+                Ok(DescriptorPool::decode(std::fs::read(path)?.as_slice())?)
+            }
+        }
+    }
+}
+
+/// Protobuf through messages looked up in a descriptor pool at runtime, points of
+/// a stream being held by its list message as picked by [`proto::list_messages`]
+pub struct ProtoReflect {
+    lists: HashMap<String, MessageDescriptor>,
+}
+
+impl ProtoReflect {
+    pub fn new(descriptor_pool: &DescriptorPool) -> Result<ProtoReflect, Error> {
+        let lists = proto::list_messages(descriptor_pool)?;
+
+        Ok(ProtoReflect { lists })
+    }
+
+    fn list_descriptor(&self, stream: &str) -> Result<MessageDescriptor, Error> {
+        self.lists
+            .get(stream)
+            .cloned()
+            .ok_or_else(|| Error::UnknownStream(stream.to_owned()))
+    }
+}
+
+impl Serializer for ProtoReflect {
+    fn name(&self) -> &str {
+        "protoref"
+    }
+
+    fn schema_streams(&self) -> Option<Vec<String>> {
+        Some(self.lists.keys().cloned().collect())
+    }
 
-    fn proto_reflect_deserialize(
+    fn serialize(&self, stream: &str, payload: Vec<Payload>) -> Result<Vec<u8>, Error> {
+        let desc = self.list_descriptor(stream)?;
+        let msg = proto::to_message(stream, &desc, &payload)?;
+
+        let mut serialized = vec![];
+        msg.encode(&mut serialized)?;
+
+        Ok(serialized)
+    }
+
+    fn deserialize(&self, stream: &str, payload: &[u8]) -> Result<Vec<Payload>, Error> {
+        let desc = self.list_descriptor(stream)?;
+        let deserialized = DynamicMessage::decode(desc.clone(), payload)?;
+
+        proto::from_message(stream, &desc, &deserialized)
+    }
+}
+
+/// Typed points of a stream as an rkyv archive, laid out as they are in memory and
+/// read in place
+pub struct Rkyv;
+
+impl Serializer for Rkyv {
+    fn name(&self) -> &str {
+        "rkyv"
+    }
+
+    fn schema_streams(&self) -> Option<Vec<String>> {
+        Some(native::STREAMS.iter().map(|s| s.to_string()).collect())
+    }
+
+    fn serialize(&self, stream: &str, payload: Vec<Payload>) -> Result<Vec<u8>, Error> {
+        native::serialize_rkyv(payload, stream)
+    }
+
+    fn deserialize(&self, stream: &str, payload: &[u8]) -> Result<Vec<Payload>, Error> {
+        native::deserialize_rkyv(payload, stream)
+    }
+
+    fn read_fields(
         &self,
-        descriptor_pool: &DescriptorPool,
-        payload: &[u8],
         stream: &str,
-    ) -> Result<Vec<Payload>, Error> {
-        let desc = descriptor_pool.get_message_by_name(stream).unwrap();
-
-        let deserialized = DynamicMessage::decode(desc, payload)?;
-        let options = SerializeOptions::new().stringify_64_bit_integers(false);
-        let mut json_serialized = vec![];
-        let mut serializer = Serializer::new(&mut json_serialized);
-        deserialized.serialize_with_options(&mut serializer, &options)?;
-        let array: PayloadArray = serde_json::from_slice(&json_serialized)?;
-
-        Ok(array.messages)
-    }
-}
-
-pub fn hard_code_proto() -> DescriptorPool {
-    let schema = r#"syntax = "proto3";
-        package test;
-        
-        message imu {
-            uint64 timestamp  = 1;
-            uint32 sequence = 2;
-            double ax = 3;
-            double ay = 4;
-            double az = 5;
-            double pitch = 6;
-            double roll = 7;
-            double yaw = 8;
-            double magx = 9;
-            double magy = 10;
-            double magz = 11;
-        }
-        
-        message imuList {
-            repeated imu messages = 1;
-        }
-        
-        message motor {
-            uint64 timestamp  = 1;
-            uint32 sequence = 2;
-            double temperature1 = 3;
-            double temperature2 = 4;
-            double temperature3 = 5;
-            double voltage = 6;
-            double current = 7;
-            uint32 rpm = 8;
-        }
-        
-        message motorList {
-            repeated motor messages = 1;
-        }
-        
-        message can {
-            uint64 timestamp = 1;
-            sint32 sequence = 2;
-            uint64 data = 3;
-        }
-        
-        message canList {
-            repeated can messages = 1;
-        }
-        
-        message gps {
-            double longitude = 1;
-            double latitude = 2;
-            uint64 timestamp = 3;
-            sint32 sequence = 4;
-        }
-        
-        message gpsList {
-            repeated gps messages = 1;
-        }
+        payload: &[u8],
+        reads: &[FieldRead],
+    ) -> Result<Vec<f64>, Error> {
+        native::read_fields_rkyv(payload, stream, reads)
+    }
+}
 
-        message bms {
-            sint32 sequence = 1;
-            uint64 timestamp = 2;
-            int32 periodicity_ms = 3;
-            double mosfet_temperature = 4;
-            double ambient_temperature = 5;
-            int32 mosfet_status = 6;
-            int32 cell_voltage_count = 7;
-            double cell_voltage_1 = 8;
-            double cell_voltage_2 = 9;
-            double cell_voltage_3 = 10;
-            double cell_voltage_4 = 11;
-            double cell_voltage_5 = 12;
-            double cell_voltage_6 = 13;
-            double cell_voltage_7 = 14;
-            double cell_voltage_8 = 15;
-            double cell_voltage_9 = 16;
-            double cell_voltage_10 = 17;
-            double cell_voltage_11 = 18;
-            double cell_voltage_12 = 19;
-            double cell_voltage_13 = 20;
-            double cell_voltage_14 = 21;
-            double cell_voltage_15 = 22;
-            double cell_voltage_16 = 23;
-            int32 cell_thermistor_count = 24;
-            double cell_temp_1 = 25;
-            double cell_temp_2 = 26;
-            double cell_temp_3 = 27;
-            double cell_temp_4 = 28;
-            double cell_temp_5 = 29;
-            double cell_temp_6 = 30;
-            double cell_temp_7 = 31;
-            double cell_temp_8 = 32;
-            int32 cell_balancing_status = 33;
-            double pack_voltage = 34;
-            double pack_current = 35;
-            double pack_soc = 36;
-            double pack_soh = 37;
-            double pack_sop = 38;
-            int64 pack_cycle_count = 39;
-            int64 pack_available_energy = 40;
-            int64 pack_consumed_energy = 41;
-            int32 pack_fault = 42;
-            int32 pack_status = 43;
-        }
-        
-        message bmsList {
-            repeated bms messages = 1;
-        }
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+    use crate::simulator::golden_points;
+
+    /// Batch of every stream, made of the golden point of the stream with its numbers
+    /// changed by the index of the point
+    fn batches() -> Vec<(String, Vec<Payload>)> {
+        golden_points()
+            .into_iter()
+            .map(|(stream, point)| {
+                let batch = (0..10u32)
+                    .map(|i| {
+                        let mut point = point.clone();
+                        point.sequence += i;
+                        point.timestamp += 100 * i as u64;
+                        for value in point.payload.as_object_mut().unwrap().values_mut() {
+                            *value = shift(value, i);
+                        }
+                        point
+                    })
+                    .collect();
+                (stream, batch)
+            })
+            .collect()
+    }
 
-        message peripherals {
-            string gps = 1;
-            string gsm = 2;
-            string imu = 3;
-            string left_indicator = 4;
-            string right_indicator = 5;
-            string headlamp = 6;
-            string horn = 7;
-            string left_brake = 8;
-            string right_brake = 9;
-            sint32 sequence = 10;
-            uint64 timestamp = 11;
+    /// Number changed by `i` while keeping its type and range, other values as is
+    fn shift(value: &Value, i: u32) -> Value {
+        if let Some(v) = value.as_u64() {
+            Value::from(v ^ i as u64)
+        } else if let Some(v) = value.as_i64() {
+            Value::from(v ^ i as i64)
+        } else if let Some(v) = value.as_f64() {
+            Value::from(v * (1.0 + i as f64 / 8.0))
+        } else {
+            value.clone()
         }
+    }
+
+    /// Sum and max of every field of the points along with one none of them has
+    fn reads(points: &[Payload]) -> Vec<FieldRead> {
+        let mut fields = vec!["sequence", "timestamp", "missing"];
+        fields.extend(
+            points[0]
+                .payload
+                .as_object()
+                .unwrap()
+                .keys()
+                .map(|k| k.as_str()),
+        );
+
+        fields
+            .into_iter()
+            .flat_map(|field| {
+                [Fold::Sum, Fold::Max].map(|fold| FieldRead {
+                    field: field.to_owned(),
+                    fold,
+                })
+            })
+            .collect()
+    }
+
+    /// Checks that reading fields in place matches folding over deserialized points,
+    /// for every stream the format has a schema for
+    fn assert_reads_match(format: &str) {
+        let registry = Registry::builtin().unwrap();
+        let serializer = registry.select(&[format.to_owned()]).unwrap()[0];
 
-        message peripheralsList {
-            repeated peripherals messages = 1;
+        for (stream, points) in batches() {
+            if !serializer.supports(&stream) {
+                continue;
+            }
+            let serialized = serializer.serialize(&stream, points.clone()).unwrap();
+            let reads = reads(&points);
+
+            let deserialized = serializer.deserialize(&stream, &serialized).unwrap();
+            let folded: Vec<f64> = reads
+                .iter()
+                .map(|r| r.fold(deserialized.iter().filter_map(|p| number(p, &r.field))))
+                .collect();
+            let read = serializer
+                .read_fields(&stream, &serialized, &reads)
+                .unwrap();
+
+            for ((r, read), folded) in reads.iter().zip(read).zip(folded) {
+                assert_eq!(read, folded, "{} {} {:?}", format, stream, r);
+            }
         }
+    }
+
+    macro_rules! reads_in_place {
+        ($($test:ident => $format:literal),* $(,)?) => {$(
+            #[test]
+            fn $test() {
+                assert_reads_match($format);
+            }
+        )*};
+    }
+
+    reads_in_place! {
+        capnproto_reads_match_deserialized => "capnproto",
+        capnproto_packed_reads_match_deserialized => "capnproto-packed",
+        flatbuffers_reads_match_deserialized => "flatbuffers",
+        flexbuffers_reads_match_deserialized => "flexbuffers",
+        arrow_reads_match_deserialized => "arrow",
+        parquet_reads_match_deserialized => "parquet",
+        rkyv_reads_match_deserialized => "rkyv",
+    }
+
+    #[test]
+    fn rkyv_reads_unaligned_archives() {
+        let registry = Registry::builtin().unwrap();
+        let serializer = registry.select(&["rkyv".to_owned()]).unwrap()[0];
 
-        message shadow {
-            string mode = 1;
-            string status = 2;
-            string firmware_version = 3;
-            string config_version = 4;
-            int64 distance_travelled = 5;
-            int64 range = 6;
-            double SOC = 7;
-            sint32 sequence = 8;
-            uint64 timestamp = 9;
+        for (stream, points) in batches() {
+            let serialized = serializer.serialize(&stream, points.clone()).unwrap();
+            // one byte into an aligned buffer is never aligned
+            let mut buffer = rkyv::util::AlignedVec::<16>::new();
+            buffer.push(0);
+            buffer.extend_from_slice(&serialized);
+            let unaligned = &buffer[1..];
+
+            let deserialized = serializer.deserialize(&stream, unaligned).unwrap();
+            assert_eq!(deserialized, points, "{}", stream);
+            let reads = reads(&points);
+            assert_eq!(
+                serializer.read_fields(&stream, unaligned, &reads).unwrap(),
+                serializer
+                    .read_fields(&stream, &serialized, &reads)
+                    .unwrap(),
+            );
         }
+    }
+
+    /// Checks that a motor point whose rpm is out of the range of its uint32 field is
+    /// a type mismatch rather than truncated into the batch
+    fn assert_out_of_range_mismatches(format: &str) {
+        let registry = Registry::builtin().unwrap();
+        let serializer = registry.select(&[format.to_owned()]).unwrap()[0];
+        let (stream, mut points) = batches().into_iter().find(|(s, _)| s == "motor").unwrap();
+        points[3].payload["rpm"] = Value::from(u64::from(u32::MAX) + 1);
 
-        message shadowList {
-            repeated shadow messages = 1;
+        match serializer.serialize(&stream, points) {
+            Err(Error::TypeMismatch { field, .. }) => assert_eq!(field, "rpm", "{}", format),
+            Err(e) => panic!("{} failed with {}", format, e),
+            Ok(_) => panic!("{} serialized an rpm out of range", format),
         }
-        "#
-    .to_string();
-
-    let proto_dir = "/tmp/beamd/protos";
-    let proto_file_path = proto_dir.to_owned() + "/schema.proto";
-    let descriptor_file_path = proto_dir.to_owned() + "/file_descriptor_set.bin";
-    std::fs::create_dir_all(proto_dir).unwrap();
-    let mut file = std::fs::File::create(&proto_file_path).unwrap();
-    file.write_all(schema.as_bytes()).unwrap();
-
-    std::env::set_var("OUT_DIR", proto_dir);
-    prost_reflect_build::Builder::new()
-        .file_descriptor_set_path(&descriptor_file_path)
-        .compile_protos(&[proto_file_path], &[proto_dir])
-        .expect("Failed to compile protos");
-
-    let mut descriptor_file = std::fs::File::open(descriptor_file_path).unwrap();
-    let mut file_descriptor_set = vec![];
-    descriptor_file
-        .read_to_end(&mut file_descriptor_set)
-        .unwrap();
-
-    DescriptorPool::decode(file_descriptor_set.as_slice()).unwrap()
-}
-
-// TODO: replace type information with following
-// "type": "record",
-// "name": "can",
-// "fields" : [
-//     {"name": "sequence", "type": "int"},
-//     {"name": "timestamp", "type": "long"},
-//     {"name": "data", "type": "long"}
-// ]
-// pub fn hard_code_avro() -> Schema {
-//     Schema::parse_str(
-//         r##"
-//     {
-//         "namespace": "test",
-//         "type": "array",
-//         "items": {
-//             "type": "map",
-//             "values": "long"
-//         }
-//     }
-// "##,
-//     )
-//     .unwrap()
-// }
+    }
+
+    macro_rules! out_of_range {
+        ($($test:ident => $format:literal),* $(,)?) => {$(
+            #[test]
+            fn $test() {
+                assert_out_of_range_mismatches($format);
+            }
+        )*};
+    }
+
+    out_of_range! {
+        capnproto_values_out_of_range_are_mismatches => "capnproto",
+        capnproto_packed_values_out_of_range_are_mismatches => "capnproto-packed",
+        protobuf_values_out_of_range_are_mismatches => "protobuf",
+        protoref_values_out_of_range_are_mismatches => "protoref",
+    }
+}
@@ -0,0 +1,242 @@
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+
+use arrow::array::{
+    ArrayRef, AsArray, BooleanArray, Float64Array, Int64Array, RecordBatch, StringArray,
+    UInt32Array, UInt64Array,
+};
+use arrow::datatypes::{DataType, Field, Float64Type, Int64Type, Schema, UInt32Type, UInt64Type};
+use arrow::ipc::{reader::StreamReader, writer::StreamWriter};
+use bytes::Bytes;
+use parquet::arrow::{arrow_reader::ParquetRecordBatchReaderBuilder, ArrowWriter, ProjectionMask};
+use serde_json::{Map, Value};
+
+use crate::base::Payload;
+
+use super::{type_mismatch, Error, FieldRead};
+
+const SEQUENCE: &str = "sequence";
+const TIMESTAMP: &str = "timestamp";
+/// Metadata key of columns holding values of mixed or nested types as json text
+const JSON: &str = "zerde.json";
+
+/// Writes a batch as an Arrow IPC stream holding a single record batch
+pub fn serialize_ipc(payload: Vec<Payload>) -> Result<Vec<u8>, Error> {
+    let batch = record_batch(&payload)?;
+    let mut writer = StreamWriter::try_new(vec![], &batch.schema())?;
+    writer.write(&batch)?;
+    writer.finish()?;
+
+    Ok(writer.into_inner()?)
+}
+
+pub fn deserialize_ipc(payload: &[u8], stream: &str) -> Result<Vec<Payload>, Error> {
+    let reader = StreamReader::try_new(payload, None)?;
+
+    let mut points = vec![];
+    for batch in reader {
+        points.extend(from_record_batch(&batch?, stream)?);
+    }
+
+    Ok(points)
+}
+
+/// Folds the columns of the fields read, leaving every other column undecoded
+pub fn read_fields_ipc(payload: &[u8], reads: &[FieldRead]) -> Result<Vec<f64>, Error> {
+    let reader = StreamReader::try_new(payload, None)?;
+
+    let mut folded: Vec<f64> = reads.iter().map(|r| r.fold.empty()).collect();
+    for batch in reader {
+        fold_columns(&batch?, reads, &mut folded);
+    }
+
+    Ok(folded)
+}
+
+/// Writes a batch as a Parquet file with a single row group
+pub fn serialize_parquet(payload: Vec<Payload>) -> Result<Vec<u8>, Error> {
+    let batch = record_batch(&payload)?;
+    let mut writer = ArrowWriter::try_new(vec![], batch.schema(), None)?;
+    writer.write(&batch)?;
+
+    Ok(writer.into_inner()?)
+}
+
+pub fn deserialize_parquet(payload: &[u8], stream: &str) -> Result<Vec<Payload>, Error> {
+    let reader =
+        ParquetRecordBatchReaderBuilder::try_new(Bytes::copy_from_slice(payload))?.build()?;
+
+    let mut points = vec![];
+    for batch in reader {
+        points.extend(from_record_batch(&batch?, stream)?);
+    }
+
+    Ok(points)
+}
+
+/// Like [`read_fields_ipc`], with only the column chunks of the fields read being
+/// decompressed and decoded
+pub fn read_fields_parquet(payload: &[u8], reads: &[FieldRead]) -> Result<Vec<f64>, Error> {
+    let builder = ParquetRecordBatchReaderBuilder::try_new(Bytes::copy_from_slice(payload))?;
+    let columns: Vec<usize> = builder
+        .schema()
+        .fields()
+        .iter()
+        .enumerate()
+        .filter(|(_, f)| reads.iter().any(|r| r.field == *f.name()))
+        .map(|(i, _)| i)
+        .collect();
+    let projection = ProjectionMask::roots(builder.parquet_schema(), columns);
+    let reader = builder.with_projection(projection).build()?;
+
+    let mut folded: Vec<f64> = reads.iter().map(|r| r.fold.empty()).collect();
+    for batch in reader {
+        fold_columns(&batch?, reads, &mut folded);
+    }
+
+    Ok(folded)
+}
+
+fn fold_columns(batch: &RecordBatch, reads: &[FieldRead], folded: &mut [f64]) {
+    for (read, folded) in reads.iter().zip(folded.iter_mut()) {
+        let Some(column) = batch.column_by_name(&read.field) else {
+            continue;
+        };
+
+        macro_rules! fold {
+            ($ty:ty) => {
+                column
+                    .as_primitive::<$ty>()
+                    .iter()
+                    .flatten()
+                    .fold(*folded, |f, v| read.fold.step(f, v as f64))
+            };
+        }
+
+        *folded = match column.data_type() {
+            DataType::UInt32 => fold!(UInt32Type),
+            DataType::UInt64 => fold!(UInt64Type),
+            DataType::Int64 => fold!(Int64Type),
+            DataType::Float64 => fold!(Float64Type),
+            _ => continue,
+        };
+    }
+}
+
+/// Record batch with a column per field of the points. Column types are inferred
+/// from the values in the batch, fields missing from some points being nullable
+pub fn record_batch(payload: &[Payload]) -> Result<RecordBatch, Error> {
+    let names: BTreeSet<&str> = payload
+        .iter()
+        .filter_map(|p| p.payload.as_object())
+        .flat_map(|o| o.keys().map(|k| k.as_str()))
+        .filter(|k| !matches!(*k, SEQUENCE | TIMESTAMP))
+        .collect();
+
+    let mut fields = vec![
+        Field::new(SEQUENCE, DataType::UInt32, false),
+        Field::new(TIMESTAMP, DataType::UInt64, false),
+    ];
+    let mut columns: Vec<ArrayRef> = vec![
+        Arc::new(UInt32Array::from_iter_values(
+            payload.iter().map(|p| p.sequence),
+        )),
+        Arc::new(UInt64Array::from_iter_values(
+            payload.iter().map(|p| p.timestamp),
+        )),
+    ];
+
+    for name in names {
+        let values: Vec<Option<&Value>> = payload.iter().map(|p| p.payload.get(name)).collect();
+        let nullable = values.iter().any(|v| v.is_none());
+        let all = |f: fn(&Value) -> bool| values.iter().flatten().all(|v| f(v));
+
+        let (data_type, column): (DataType, ArrayRef) = if all(Value::is_f64) {
+            let column: Float64Array = values.iter().map(|v| v.and_then(Value::as_f64)).collect();
+            (DataType::Float64, Arc::new(column))
+        } else if all(Value::is_i64) {
+            let column: Int64Array = values.iter().map(|v| v.and_then(Value::as_i64)).collect();
+            (DataType::Int64, Arc::new(column))
+        } else if all(Value::is_u64) {
+            let column: UInt64Array = values.iter().map(|v| v.and_then(Value::as_u64)).collect();
+            (DataType::UInt64, Arc::new(column))
+        } else if all(Value::is_boolean) {
+            let column: BooleanArray = values.iter().map(|v| v.and_then(Value::as_bool)).collect();
+            (DataType::Boolean, Arc::new(column))
+        } else if all(Value::is_string) {
+            let column: StringArray = values.iter().map(|v| v.and_then(Value::as_str)).collect();
+            (DataType::Utf8, Arc::new(column))
+        } else {
+            let column: StringArray = values.iter().map(|v| v.map(Value::to_string)).collect();
+            let metadata = HashMap::from([(JSON.to_owned(), "true".to_owned())]);
+            fields.push(Field::new(name, DataType::Utf8, nullable).with_metadata(metadata));
+            columns.push(Arc::new(column));
+            continue;
+        };
+
+        fields.push(Field::new(name, data_type, nullable));
+        columns.push(column);
+    }
+
+    Ok(RecordBatch::try_new(
+        Arc::new(Schema::new(fields)),
+        columns,
+    )?)
+}
+
+/// Points of a record batch, a row per point
+pub fn from_record_batch(batch: &RecordBatch, stream: &str) -> Result<Vec<Payload>, Error> {
+    let mut points = vec![Payload::default(); batch.num_rows()];
+    let mut payloads = vec![Map::new(); batch.num_rows()];
+
+    let schema = batch.schema();
+    for (field, column) in schema.fields().iter().zip(batch.columns()) {
+        match (field.name().as_str(), field.data_type()) {
+            (SEQUENCE, DataType::UInt32) => {
+                let values = column.as_primitive::<UInt32Type>().values();
+                for (point, sequence) in points.iter_mut().zip(values.iter()) {
+                    point.sequence = *sequence;
+                }
+            }
+            (TIMESTAMP, DataType::UInt64) => {
+                let values = column.as_primitive::<UInt64Type>().values();
+                for (point, timestamp) in points.iter_mut().zip(values.iter()) {
+                    point.timestamp = *timestamp;
+                }
+            }
+            (name, data_type) => {
+                let values: Vec<Option<Value>> = match data_type {
+                    DataType::Float64 => collect(column.as_primitive::<Float64Type>().iter()),
+                    DataType::Int64 => collect(column.as_primitive::<Int64Type>().iter()),
+                    DataType::UInt64 => collect(column.as_primitive::<UInt64Type>().iter()),
+                    DataType::Boolean => collect(column.as_boolean().iter()),
+                    DataType::Utf8 if field.metadata().contains_key(JSON) => column
+                        .as_string::<i32>()
+                        .iter()
+                        .map(|v| v.map(serde_json::from_str).transpose())
+                        .collect::<Result<_, _>>()?,
+                    DataType::Utf8 => collect(column.as_string::<i32>().iter()),
+                    data_type => {
+                        return Err(type_mismatch(stream, name, "a scalar column", data_type))
+                    }
+                };
+
+                for (payload, value) in payloads.iter_mut().zip(values) {
+                    if let Some(value) = value {
+                        payload.insert(name.to_owned(), value);
+                    }
+                }
+            }
+        }
+    }
+
+    for (point, payload) in points.iter_mut().zip(payloads) {
+        point.payload = Value::Object(payload);
+    }
+
+    Ok(points)
+}
+
+fn collect<T: Into<Value>>(values: impl Iterator<Item = Option<T>>) -> Vec<Option<Value>> {
+    values.map(|v| v.map(Into::into)).collect()
+}
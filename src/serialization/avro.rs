@@ -0,0 +1,322 @@
+use std::collections::HashMap;
+
+use apache_avro::{from_avro_datum, to_avro_datum, types::Value, Reader, Schema, Writer};
+use serde_json::Map;
+
+use crate::base::Payload;
+
+use super::{json_type, type_mismatch, Error};
+
+const GPS: &str = r#"{
+    "type": "record",
+    "name": "gps",
+    "namespace": "test",
+    "fields": [
+        {"name": "longitude", "type": "double"},
+        {"name": "latitude", "type": "double"},
+        {"name": "timestamp", "type": "long"},
+        {"name": "sequence", "type": "int"}
+    ]
+}"#;
+
+const IMU: &str = r#"{
+    "type": "record",
+    "name": "imu",
+    "namespace": "test",
+    "fields": [
+        {"name": "timestamp", "type": "long"},
+        {"name": "sequence", "type": "int"},
+        {"name": "ax", "type": "double"},
+        {"name": "ay", "type": "double"},
+        {"name": "az", "type": "double"},
+        {"name": "pitch", "type": "double"},
+        {"name": "roll", "type": "double"},
+        {"name": "yaw", "type": "double"},
+        {"name": "magx", "type": "double"},
+        {"name": "magy", "type": "double"},
+        {"name": "magz", "type": "double"}
+    ]
+}"#;
+
+const BMS: &str = r#"{
+    "type": "record",
+    "name": "bms",
+    "namespace": "test",
+    "fields": [
+        {"name": "sequence", "type": "int"},
+        {"name": "timestamp", "type": "long"},
+        {"name": "periodicity_ms", "type": "int"},
+        {"name": "mosfet_temperature", "type": "double"},
+        {"name": "ambient_temperature", "type": "double"},
+        {"name": "mosfet_status", "type": "int"},
+        {"name": "cell_voltage_count", "type": "int"},
+        {"name": "cell_voltage_1", "type": "double"},
+        {"name": "cell_voltage_2", "type": "double"},
+        {"name": "cell_voltage_3", "type": "double"},
+        {"name": "cell_voltage_4", "type": "double"},
+        {"name": "cell_voltage_5", "type": "double"},
+        {"name": "cell_voltage_6", "type": "double"},
+        {"name": "cell_voltage_7", "type": "double"},
+        {"name": "cell_voltage_8", "type": "double"},
+        {"name": "cell_voltage_9", "type": "double"},
+        {"name": "cell_voltage_10", "type": "double"},
+        {"name": "cell_voltage_11", "type": "double"},
+        {"name": "cell_voltage_12", "type": "double"},
+        {"name": "cell_voltage_13", "type": "double"},
+        {"name": "cell_voltage_14", "type": "double"},
+        {"name": "cell_voltage_15", "type": "double"},
+        {"name": "cell_voltage_16", "type": "double"},
+        {"name": "cell_thermistor_count", "type": "int"},
+        {"name": "cell_temp_1", "type": "double"},
+        {"name": "cell_temp_2", "type": "double"},
+        {"name": "cell_temp_3", "type": "double"},
+        {"name": "cell_temp_4", "type": "double"},
+        {"name": "cell_temp_5", "type": "double"},
+        {"name": "cell_temp_6", "type": "double"},
+        {"name": "cell_temp_7", "type": "double"},
+        {"name": "cell_temp_8", "type": "double"},
+        {"name": "cell_balancing_status", "type": "int"},
+        {"name": "pack_voltage", "type": "double"},
+        {"name": "pack_current", "type": "double"},
+        {"name": "pack_soc", "type": "double"},
+        {"name": "pack_soh", "type": "double"},
+        {"name": "pack_sop", "type": "double"},
+        {"name": "pack_cycle_count", "type": "long"},
+        {"name": "pack_available_energy", "type": "long"},
+        {"name": "pack_consumed_energy", "type": "long"},
+        {"name": "pack_fault", "type": "int"},
+        {"name": "pack_status", "type": "int"}
+    ]
+}"#;
+
+const PERIPHERALS: &str = r#"{
+    "type": "record",
+    "name": "peripherals",
+    "namespace": "test",
+    "fields": [
+        {"name": "gps", "type": "string"},
+        {"name": "gsm", "type": "string"},
+        {"name": "imu", "type": "string"},
+        {"name": "left_indicator", "type": "string"},
+        {"name": "right_indicator", "type": "string"},
+        {"name": "headlamp", "type": "string"},
+        {"name": "horn", "type": "string"},
+        {"name": "left_brake", "type": "string"},
+        {"name": "right_brake", "type": "string"},
+        {"name": "sequence", "type": "int"},
+        {"name": "timestamp", "type": "long"}
+    ]
+}"#;
+
+const MOTOR: &str = r#"{
+    "type": "record",
+    "name": "motor",
+    "namespace": "test",
+    "fields": [
+        {"name": "timestamp", "type": "long"},
+        {"name": "sequence", "type": "int"},
+        {"name": "temperature1", "type": "double"},
+        {"name": "temperature2", "type": "double"},
+        {"name": "temperature3", "type": "double"},
+        {"name": "voltage", "type": "double"},
+        {"name": "current", "type": "double"},
+        {"name": "rpm", "type": "int"}
+    ]
+}"#;
+
+const CAN: &str = r#"{
+    "type": "record",
+    "name": "can",
+    "namespace": "test",
+    "fields": [
+        {"name": "timestamp", "type": "long"},
+        {"name": "sequence", "type": "int"},
+        {"name": "data", "type": "long"}
+    ]
+}"#;
+
+const SHADOW: &str = r#"{
+    "type": "record",
+    "name": "shadow",
+    "namespace": "test",
+    "fields": [
+        {"name": "mode", "type": "string"},
+        {"name": "status", "type": "string"},
+        {"name": "firmware_version", "type": "string"},
+        {"name": "config_version", "type": "string"},
+        {"name": "distance_travelled", "type": "long"},
+        {"name": "range", "type": "long"},
+        {"name": "soc", "type": "double"},
+        {"name": "sequence", "type": "int"},
+        {"name": "timestamp", "type": "long"}
+    ]
+}"#;
+
+/// Schemas of a stream, for a single point and for a batch of points
+pub struct StreamSchema {
+    pub stream: String,
+    pub record: Schema,
+    pub batch: Schema,
+}
+
+/// Record schemas of every stream, keyed by stream name
+pub fn schemas() -> Result<HashMap<String, StreamSchema>, Error> {
+    [
+        ("gps", GPS),
+        ("imu", IMU),
+        ("bms", BMS),
+        ("peripherals", PERIPHERALS),
+        ("motor", MOTOR),
+        ("can", CAN),
+        ("shadow", SHADOW),
+    ]
+    .into_iter()
+    .map(|(stream, schema)| {
+        let record = Schema::parse_str(schema)?;
+        let batch = Schema::Array(Box::new(record.clone()));
+
+        let schema = StreamSchema {
+            stream: stream.to_owned(),
+            record,
+            batch,
+        };
+
+        Ok((stream.to_owned(), schema))
+    })
+    .collect()
+}
+
+/// Encodes a batch as a single datum, an array of records
+pub fn serialize_datum(payload: Vec<Payload>, schema: &StreamSchema) -> Result<Vec<u8>, Error> {
+    let records = payload
+        .iter()
+        .map(|p| to_record(p, schema))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(to_avro_datum(&schema.batch, Value::Array(records))?)
+}
+
+pub fn deserialize_datum(mut payload: &[u8], schema: &StreamSchema) -> Result<Vec<Payload>, Error> {
+    match from_avro_datum(&schema.batch, &mut payload, None)? {
+        Value::Array(records) => records
+            .into_iter()
+            .map(|r| from_record(r, &schema.stream))
+            .collect(),
+        _ => Err(Error::AvroMissing),
+    }
+}
+
+/// Writes a batch into an object container file, a record per point
+pub fn serialize_container(payload: Vec<Payload>, schema: &StreamSchema) -> Result<Vec<u8>, Error> {
+    let mut writer = Writer::new(&schema.record, vec![]);
+    for p in payload.iter() {
+        writer.append(to_record(p, schema)?)?;
+    }
+
+    Ok(writer.into_inner()?)
+}
+
+pub fn deserialize_container(payload: &[u8], schema: &StreamSchema) -> Result<Vec<Payload>, Error> {
+    let reader = Reader::with_schema(&schema.record, payload)?;
+
+    reader
+        .map(|record| from_record(record?, &schema.stream))
+        .collect()
+}
+
+fn to_record(point: &Payload, schema: &StreamSchema) -> Result<Value, Error> {
+    let Schema::Record { fields, .. } = &schema.record else {
+        return Err(Error::AvroMissing);
+    };
+
+    let mut record = Vec::with_capacity(fields.len());
+    for field in fields {
+        let json = match field.name.as_str() {
+            "sequence" => serde_json::Value::from(point.sequence),
+            "timestamp" => serde_json::Value::from(point.timestamp),
+            name => match point.payload.get(name) {
+                Some(value) => value.clone(),
+                None => {
+                    return Err(Error::MissingField {
+                        stream: schema.stream.clone(),
+                        field: name.to_owned(),
+                    })
+                }
+            },
+        };
+
+        let value = match &field.schema {
+            Schema::Int => match json.as_i64().map(i32::try_from) {
+                Some(Ok(v)) => Some(Value::Int(v)),
+                Some(Err(_)) => {
+                    return Err(type_mismatch(
+                        &schema.stream,
+                        &field.name,
+                        "a 32 bit int",
+                        &json,
+                    ))
+                }
+                None => None,
+            },
+            Schema::Long => json.as_i64().map(Value::Long),
+            Schema::Double => json.as_f64().map(Value::Double),
+            Schema::String => json.as_str().map(|v| Value::String(v.to_owned())),
+            _ => None,
+        };
+        let Some(value) = value else {
+            return Err(type_mismatch(
+                &schema.stream,
+                &field.name,
+                format!("{:?}", field.schema),
+                json_type(&json),
+            ));
+        };
+        record.push((field.name.clone(), value));
+    }
+
+    Ok(Value::Record(record))
+}
+
+fn from_record(record: Value, stream: &str) -> Result<Payload, Error> {
+    let Value::Record(fields) = record else {
+        return Err(Error::AvroMissing);
+    };
+
+    let mut point = Payload::default();
+    let mut payload = Map::new();
+    for (name, value) in fields {
+        match value {
+            Value::Int(v) if name == "sequence" => {
+                point.sequence =
+                    u32::try_from(v).map_err(|_| type_mismatch(stream, &name, "a u32", v))?
+            }
+            Value::Long(v) if name == "timestamp" => {
+                point.timestamp =
+                    u64::try_from(v).map_err(|_| type_mismatch(stream, &name, "a u64", v))?
+            }
+            Value::Int(v) => {
+                payload.insert(name, v.into());
+            }
+            Value::Long(v) => {
+                payload.insert(name, v.into());
+            }
+            Value::Double(v) => {
+                payload.insert(name, v.into());
+            }
+            Value::String(v) => {
+                payload.insert(name, v.into());
+            }
+            value => {
+                return Err(type_mismatch(
+                    stream,
+                    &name,
+                    "a scalar",
+                    format!("{:?}", value),
+                ))
+            }
+        }
+    }
+    point.payload = serde_json::Value::Object(payload);
+
+    Ok(point)
+}
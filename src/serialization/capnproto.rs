@@ -1,224 +1,351 @@
 use capnp::{
-    message::{ReaderOptions, TypedBuilder},
+    dynamic_struct, dynamic_value,
+    introspect::TypeVariant,
+    message::{Builder, HeapAllocator, Reader, ReaderOptions, ReaderSegments},
+    schema::{Field, StructSchema},
     serialize::{read_message, write_message},
+    serialize_packed,
 };
-use serde_json::json;
+use serde_json::{Map, Value};
 
-use crate::{
-    base::Payload,
-    test_capnp::{bms_list, gps_list, imu_list, peripherals_list},
-};
+use crate::base::Payload;
+
+use super::{json_type, set_point_field, type_mismatch, Error, FieldRead};
+
+mod streams {
+    include!(concat!(env!("OUT_DIR"), "/capnp_streams.rs"));
+}
+
+pub use streams::STREAMS;
 
-use super::Error;
+/// Field of a list struct holding its points
+const MESSAGES: &str = "messages";
 
 pub fn serialize(payload: Vec<Payload>, stream: &str) -> Result<Vec<u8>, Error> {
+    let message = build_message(&payload, stream)?;
     let mut buf = vec![];
-    match stream {
-        "test.gpsList" => {
-            let mut message = TypedBuilder::<gps_list::Owned>::new_default();
-            let mut gps_list = message.init_root().init_messages(payload.len() as u32);
-            for g in 0..payload.len() {
-                let mut gps = gps_list.reborrow().get(g as u32);
-                gps.set_sequence(payload[g].sequence as i32);
-                gps.set_timestamp(payload[g].timestamp);
-                gps.set_latitude(payload[g].payload["latitude"].as_f64().unwrap());
-                gps.set_longitude(payload[g].payload["longitude"].as_f64().unwrap());
-            }
+    write_message(&mut buf, &message)?;
 
-            write_message(&mut buf, message.borrow_inner())?;
-        }
-        "test.imuList" => {
-            let mut message = TypedBuilder::<imu_list::Owned>::new_default();
-            let mut imu_list = message.init_root().init_messages(payload.len() as u32);
-            for i in 0..payload.len() {
-                let mut imu = imu_list.reborrow().get(i as u32);
-                imu.set_sequence(payload[i].sequence);
-                imu.set_timestamp(payload[i].timestamp);
-                imu.set_ax(payload[i].payload["ax"].as_f64().unwrap());
-                imu.set_ay(payload[i].payload["ay"].as_f64().unwrap());
-                imu.set_az(payload[i].payload["az"].as_f64().unwrap());
-                imu.set_pitch(payload[i].payload["pitch"].as_f64().unwrap());
-                imu.set_roll(payload[i].payload["roll"].as_f64().unwrap());
-                imu.set_yaw(payload[i].payload["yaw"].as_f64().unwrap());
-                imu.set_magx(payload[i].payload["magx"].as_f64().unwrap());
-                imu.set_magy(payload[i].payload["magy"].as_f64().unwrap());
-                imu.set_magz(payload[i].payload["magz"].as_f64().unwrap());
-            }
+    Ok(buf)
+}
 
-            write_message(&mut buf, message.borrow_inner())?;
-        }
-        "test.peripheralsList" => {
-            let mut message = TypedBuilder::<peripherals_list::Owned>::new_default();
-            let mut peripherals_list = message.init_root().init_messages(payload.len() as u32);
-            for m in 0..payload.len() {
-                let mut peripherals = peripherals_list.reborrow().get(m as u32);
-                peripherals.set_sequence(payload[m].sequence);
-                peripherals.set_timestamp(payload[m].timestamp);
-                peripherals.set_gps(payload[m].payload["gps"].as_str().unwrap());
-                peripherals.set_gsm(payload[m].payload["gsm"].as_str().unwrap());
-                peripherals.set_imu(payload[m].payload["imu"].as_str().unwrap());
-                peripherals
-                    .set_right_indicator(payload[m].payload["right_indicator"].as_str().unwrap());
-                peripherals
-                    .set_left_indicator(payload[m].payload["left_indicator"].as_str().unwrap());
-                peripherals.set_horn(payload[m].payload["horn"].as_str().unwrap());
-                peripherals.set_headlamp(payload[m].payload["headlamp"].as_str().unwrap());
-                peripherals.set_right_brake(payload[m].payload["right_brake"].as_str().unwrap());
-                peripherals.set_left_brake(payload[m].payload["left_brake"].as_str().unwrap());
-            }
+pub fn deserialize(payload: &[u8], stream: &str) -> Result<Vec<Payload>, Error> {
+    let message = read_message(payload, ReaderOptions::new())?;
+    read_message_points(&message, stream)
+}
 
-            write_message(&mut buf, message.borrow_inner())?;
-        }
-        "test.bmsList" => {
-            let mut message = TypedBuilder::<bms_list::Owned>::new_default();
-            let mut bms_list = message.init_root().init_messages(payload.len() as u32);
-            for b in 0..payload.len() {
-                let mut bms = bms_list.reborrow().get(b as u32);
-                bms.set_sequence(payload[b].sequence as i32);
-                bms.set_timestamp(payload[b].timestamp);
-                bms.set_periodicity_ms(
-                    payload[b].payload["periodicity_ms"].as_i64().unwrap() as i32
-                );
-                bms.set_mosfet_temperature(
-                    payload[b].payload["mosfet_temperature"].as_f64().unwrap(),
-                );
-                bms.set_ambient_temperature(
-                    payload[b].payload["ambient_temperature"].as_f64().unwrap(),
-                );
-                bms.set_mosfet_status(payload[b].payload["mosfet_status"].as_i64().unwrap() as i32);
-                bms.set_cell_voltage_count(
-                    payload[b].payload["cell_voltage_count"].as_i64().unwrap() as i32,
-                );
-                bms.set_cell_voltage1(payload[b].payload["cell_voltage_1"].as_f64().unwrap());
-                bms.set_cell_voltage2(payload[b].payload["cell_voltage_2"].as_f64().unwrap());
-                bms.set_cell_voltage2(payload[b].payload["cell_voltage_2"].as_f64().unwrap());
-                bms.set_cell_voltage3(payload[b].payload["cell_voltage_3"].as_f64().unwrap());
-                bms.set_cell_voltage4(payload[b].payload["cell_voltage_4"].as_f64().unwrap());
-                bms.set_cell_voltage5(payload[b].payload["cell_voltage_5"].as_f64().unwrap());
-                bms.set_cell_voltage6(payload[b].payload["cell_voltage_6"].as_f64().unwrap());
-                bms.set_cell_voltage7(payload[b].payload["cell_voltage_7"].as_f64().unwrap());
-                bms.set_cell_voltage8(payload[b].payload["cell_voltage_8"].as_f64().unwrap());
-                bms.set_cell_voltage9(payload[b].payload["cell_voltage_9"].as_f64().unwrap());
-                bms.set_cell_voltage10(payload[b].payload["cell_voltage_10"].as_f64().unwrap());
-                bms.set_cell_voltage11(payload[b].payload["cell_voltage_11"].as_f64().unwrap());
-                bms.set_cell_voltage12(payload[b].payload["cell_voltage_12"].as_f64().unwrap());
-                bms.set_cell_voltage13(payload[b].payload["cell_voltage_13"].as_f64().unwrap());
-                bms.set_cell_voltage14(payload[b].payload["cell_voltage_14"].as_f64().unwrap());
-                bms.set_cell_voltage15(payload[b].payload["cell_voltage_15"].as_f64().unwrap());
-                bms.set_cell_voltage16(payload[b].payload["cell_voltage_16"].as_f64().unwrap());
-                bms.set_cell_thermistor_count(
-                    payload[b].payload["cell_thermistor_count"]
-                        .as_i64()
-                        .unwrap() as i32,
-                );
-                bms.set_cell_temp1(payload[b].payload["cell_temp_1"].as_f64().unwrap());
-                bms.set_cell_temp2(payload[b].payload["cell_temp_2"].as_f64().unwrap());
-                bms.set_cell_temp3(payload[b].payload["cell_temp_3"].as_f64().unwrap());
-                bms.set_cell_temp4(payload[b].payload["cell_temp_4"].as_f64().unwrap());
-                bms.set_cell_temp5(payload[b].payload["cell_temp_5"].as_f64().unwrap());
-                bms.set_cell_temp6(payload[b].payload["cell_temp_6"].as_f64().unwrap());
-                bms.set_cell_temp7(payload[b].payload["cell_temp_7"].as_f64().unwrap());
-                bms.set_cell_temp8(payload[b].payload["cell_temp_8"].as_f64().unwrap());
-                bms.set_cell_balancing_status(
-                    payload[b].payload["cell_balancing_status"]
-                        .as_i64()
-                        .unwrap() as i32,
-                );
-                bms.set_pack_voltage(payload[b].payload["pack_voltage"].as_f64().unwrap());
-                bms.set_pack_current(payload[b].payload["pack_current"].as_f64().unwrap());
-                bms.set_pack_soc(payload[b].payload["pack_soc"].as_f64().unwrap());
-                bms.set_pack_soh(payload[b].payload["pack_soh"].as_f64().unwrap());
-                bms.set_pack_sop(payload[b].payload["pack_sop"].as_f64().unwrap());
-                bms.set_pack_cycle_count(payload[b].payload["pack_cycle_count"].as_i64().unwrap());
-                bms.set_pack_available_energy(
-                    payload[b].payload["pack_available_energy"]
-                        .as_i64()
-                        .unwrap(),
-                );
-                bms.set_pack_consumed_energy(
-                    payload[b].payload["pack_consumed_energy"].as_i64().unwrap(),
-                );
-                bms.set_pack_fault(payload[b].payload["pack_fault"].as_i64().unwrap() as i32);
-                bms.set_pack_status(payload[b].payload["pack_status"].as_i64().unwrap() as i32);
-            }
+/// Like [`serialize`], with runs of zero bytes in the words of the message packed
+pub fn serialize_packed(payload: Vec<Payload>, stream: &str) -> Result<Vec<u8>, Error> {
+    let message = build_message(&payload, stream)?;
+    let mut buf = vec![];
+    serialize_packed::write_message(&mut buf, &message)?;
 
-            write_message(&mut buf, message.borrow_inner())?;
+    Ok(buf)
+}
+
+pub fn deserialize_packed(payload: &[u8], stream: &str) -> Result<Vec<Payload>, Error> {
+    let message = serialize_packed::read_message(payload, ReaderOptions::new())?;
+    read_message_points(&message, stream)
+}
+
+/// Folds fields of the points straight from the message, `packed` being whether it
+/// was written by [`serialize_packed`]
+pub fn read_fields(
+    payload: &[u8],
+    stream: &str,
+    reads: &[FieldRead],
+    packed: bool,
+) -> Result<Vec<f64>, Error> {
+    if packed {
+        let message = serialize_packed::read_message(payload, ReaderOptions::new())?;
+        read_message_fields(&message, stream, reads)
+    } else {
+        let message = read_message(payload, ReaderOptions::new())?;
+        read_message_fields(&message, stream, reads)
+    }
+}
+
+fn build_message(payload: &[Payload], stream: &str) -> Result<Builder<HeapAllocator>, Error> {
+    let mut message = Builder::new_default();
+    match streams::init_root(&mut message, stream) {
+        Some(dynamic_value::Builder::Struct(root)) => write_points(stream, payload, root)?,
+        _ => return Err(Error::UnknownStream(stream.to_owned())),
+    }
+
+    Ok(message)
+}
+
+fn read_message_points<S: ReaderSegments>(
+    message: &Reader<S>,
+    stream: &str,
+) -> Result<Vec<Payload>, Error> {
+    match streams::get_root(message, stream).transpose()? {
+        Some(dynamic_value::Reader::Struct(root)) => read_points(stream, root),
+        _ => Err(Error::UnknownStream(stream.to_owned())),
+    }
+}
+
+/// Fills the `messages` list of a list struct with points. Any struct with such a
+/// list works, e.g. the root of a message of a schema compiled by another crate
+pub fn write_points(
+    stream: &str,
+    payload: &[Payload],
+    root: dynamic_struct::Builder<'_>,
+) -> Result<(), Error> {
+    let Some(field) = root.get_schema().get_field_by_name(MESSAGES)? else {
+        return Err(Error::UnknownMessage(stream.to_owned()));
+    };
+    let dynamic_value::Builder::List(mut list) = root.initn(field, payload.len() as u32)? else {
+        return Err(Error::UnknownMessage(stream.to_owned()));
+    };
+
+    let mut keys = vec![];
+    for (i, point) in payload.iter().enumerate() {
+        let dynamic_value::Builder::Struct(mut builder) = list.reborrow().get(i as u32)? else {
+            return Err(Error::UnknownMessage(stream.to_owned()));
+        };
+        if i == 0 {
+            keys = payload_keys(builder.get_schema())?;
         }
 
-        _ => {
-            panic!("Couldn't serialize for stream: {}!", stream)
+        for (field, key) in keys.iter() {
+            let value = match key.as_str() {
+                "sequence" => Value::from(point.sequence),
+                "timestamp" => Value::from(point.timestamp),
+                key => match point.payload.get(key) {
+                    Some(value) => value.clone(),
+                    None => {
+                        return Err(Error::MissingField {
+                            stream: stream.to_owned(),
+                            field: key.to_owned(),
+                        })
+                    }
+                },
+            };
+            set_field(stream, &mut builder, *field, key, &value)?;
         }
     }
 
-    Ok(buf)
+    Ok(())
 }
 
-pub fn deserialize(payload: &[u8], stream: &str) -> Result<Vec<Payload>, Error> {
-    match stream {
-        "test.gpsList" => {
-            let message = read_message(payload, ReaderOptions::new())?;
-            let gps_list = message.get_root::<gps_list::Reader>()?.get_messages()?;
-            let mut payload = vec![];
-            for gps in gps_list {
-                payload.push(Payload {
-                    sequence: gps.get_sequence() as u32,
-                    timestamp: gps.get_timestamp(),
-                    payload: json!({"latitude": gps.get_latitude(), "longitude":gps.get_longitude(),}),
-                    ..Default::default()
-                });
-            }
+/// Points in the `messages` list of a list struct
+pub fn read_points(stream: &str, root: dynamic_struct::Reader<'_>) -> Result<Vec<Payload>, Error> {
+    let dynamic_value::Reader::List(list) = root.get_named(MESSAGES)? else {
+        return Err(Error::UnknownMessage(stream.to_owned()));
+    };
 
-            Ok(payload)
+    let mut points = Vec::with_capacity(list.len() as usize);
+    let mut keys = vec![];
+    for (i, item) in list.iter().enumerate() {
+        let dynamic_value::Reader::Struct(item) = item? else {
+            return Err(Error::UnknownMessage(stream.to_owned()));
+        };
+        if i == 0 {
+            keys = payload_keys(item.get_schema())?;
         }
-        "test.imuList" => {
-            let message = read_message(payload, ReaderOptions::new())?;
-            let imu_list = message.get_root::<imu_list::Reader>()?.get_messages()?;
-            let mut payload = vec![];
-            for imu in imu_list {
-                payload.push(Payload {
-                    sequence: imu.get_sequence(),
-                    timestamp: imu.get_timestamp(),
-                    payload: json!({"ax": imu.get_ax(), "ay": imu.get_ay(), "az": imu.get_az(), "pitch": imu.get_pitch(), "roll": imu.get_roll(), "yaw": imu.get_yaw(), "magx": imu.get_magx(), "magy": imu.get_magy(), "magz": imu.get_magz() }),
-                    ..Default::default()
-                });
-            }
 
-            Ok(payload)
+        let mut point = Payload::default();
+        let mut payload = Map::new();
+        for (field, key) in keys.iter() {
+            let value = get_field(stream, key, item.get(*field)?)?;
+            set_point_field(stream, &mut point, &mut payload, key, value)?;
         }
-        "test.peripheralsList" => {
-            let message = read_message(payload, ReaderOptions::new())?;
-            let peripherals_list = message
-                .get_root::<peripherals_list::Reader>()?
-                .get_messages()?;
-            let mut payload = vec![];
-            for peripherals in peripherals_list {
-                payload.push(Payload {
-                    sequence: peripherals.get_sequence(),
-                    timestamp: peripherals.get_timestamp(),
-                    payload: json!({ "gps": peripherals.get_gps()?, "gsm":peripherals.get_gsm()?, "imu": peripherals.get_imu()?, "right_indicator": peripherals.get_right_indicator()?, "left_indicator": peripherals.get_left_indicator()?, "horn": peripherals.get_horn()?, "headlamp": peripherals.get_headlamp()?, "right_brake": peripherals.get_right_brake()?, "left_brake": peripherals.get_left_brake()? }),
-                    ..Default::default()
-                });
-            }
+        point.payload = Value::Object(payload);
+        points.push(point);
+    }
 
-            Ok(payload)
+    Ok(points)
+}
+
+fn read_message_fields<S: ReaderSegments>(
+    message: &Reader<S>,
+    stream: &str,
+    reads: &[FieldRead],
+) -> Result<Vec<f64>, Error> {
+    let Some(dynamic_value::Reader::Struct(root)) =
+        streams::get_root(message, stream).transpose()?
+    else {
+        return Err(Error::UnknownStream(stream.to_owned()));
+    };
+    let dynamic_value::Reader::List(list) = root.get_named(MESSAGES)? else {
+        return Err(Error::UnknownMessage(stream.to_owned()));
+    };
+
+    let mut folded: Vec<f64> = reads.iter().map(|r| r.fold.empty()).collect();
+    let mut fields = vec![];
+    for (i, item) in list.iter().enumerate() {
+        let dynamic_value::Reader::Struct(item) = item? else {
+            return Err(Error::UnknownMessage(stream.to_owned()));
+        };
+        if i == 0 {
+            // field of every read, fields the struct doesn't have folding to empty
+            let keys = payload_keys(item.get_schema())?;
+            fields = reads
+                .iter()
+                .map(|r| {
+                    keys.iter()
+                        .find(|(_, key)| *key == r.field)
+                        .map(|(f, _)| *f)
+                })
+                .collect();
         }
-        "test.bmsList" => {
-            let message = read_message(payload, ReaderOptions::new())?;
-            let bms_list = message.get_root::<bms_list::Reader>()?.get_messages()?;
-            let mut payload = vec![];
-            for bms in bms_list {
-                payload.push(Payload {
-                    sequence: bms.get_sequence() as u32,
-                    timestamp: bms.get_timestamp(),
-                    payload: json!({ "periodicity_ms": bms.get_periodicity_ms(), "mosfet_temperature": bms.get_mosfet_temperature(), "ambient_temperature": bms.get_ambient_temperature(), "mosfet_status": bms.get_mosfet_status(), "cell_voltage_count": bms.get_cell_voltage_count(), "cell_voltage_1": bms.get_cell_voltage1(), "cell_voltage_2": bms.get_cell_voltage2(), "cell_voltage_3":bms.get_cell_voltage3(), "cell_voltage_4": bms.get_cell_voltage4(), "cell_voltage_5": bms.get_cell_voltage5(), "cell_voltage_6": bms.get_cell_voltage6(), "cell_voltage_7": bms.get_cell_voltage7(), "cell_voltage_8": bms.get_cell_voltage8(), "cell_voltage_9": bms.get_cell_voltage9(), "cell_voltage_10": bms.get_cell_voltage10(), "cell_voltage_11": bms.get_cell_voltage11(), "cell_voltage_12": bms.get_cell_voltage12(), "cell_voltage_13": bms.get_cell_voltage13(), "cell_voltage_14": bms.get_cell_voltage14(), "cell_voltage_15": bms.get_cell_voltage15(), "cell_voltage_16": bms.get_cell_voltage16(), "cell_thermistor_count": bms.get_cell_thermistor_count(), "cell_temp_1": bms.get_cell_temp1(), "cell_temp_2": bms.get_cell_temp2(), "cell_temp_3": bms.get_cell_temp3(), "cell_temp_4": bms.get_cell_temp4(), "cell_temp_5": bms.get_cell_temp5(), "cell_temp_6": bms.get_cell_temp6(), "cell_temp_7": bms.get_cell_temp7(), "cell_temp_8": bms.get_cell_temp8(), "cell_balancing_status": bms.get_cell_balancing_status(), "pack_voltage": bms.get_pack_voltage(), "pack_current": bms.get_pack_current(), "pack_soc": bms.get_pack_soc(), "pack_soh": bms.get_pack_soh(), "pack_sop": bms.get_pack_sop(), "pack_cycle_count": bms.get_pack_cycle_count(), "pack_available_energy": bms.get_pack_available_energy(), "pack_consumed_energy": bms.get_pack_consumed_energy(), "pack_fault": bms.get_pack_fault(), "pack_status": bms.get_pack_status(),}),
-                    ..Default::default()
-                });
-            }
 
-            Ok(payload)
+        for ((read, field), folded) in reads.iter().zip(fields.iter()).zip(folded.iter_mut()) {
+            let Some(field) = field else {
+                continue;
+            };
+            if let Some(value) = number(item.get(*field)?) {
+                *folded = read.fold.step(*folded, value);
+            }
         }
-        _ => {
-            panic!("Couldn't deserialize for stream: {}!", stream)
+    }
+
+    Ok(folded)
+}
+
+/// Key of a payload field, e.g. `cellVoltage1` is `cell_voltage_1` while `temperature1`
+/// stays as is. Cap'n Proto names are camelCase and can't hold underscores, so a
+/// number is split off only from names made of several words
+pub fn payload_key(name: &str) -> String {
+    let words = name.chars().any(|c| c.is_ascii_uppercase());
+    let mut key = String::with_capacity(name.len() + 4);
+    let mut previous = None;
+    for c in name.chars() {
+        if c.is_ascii_uppercase() {
+            key.push('_');
+            key.push(c.to_ascii_lowercase());
+        } else if words && c.is_ascii_digit() && previous.is_some_and(|p: char| !p.is_ascii_digit())
+        {
+            key.push('_');
+            key.push(c);
+        } else {
+            key.push(c);
         }
+        previous = Some(c);
     }
+
+    key
+}
+
+fn payload_keys(schema: StructSchema) -> Result<Vec<(Field, String)>, Error> {
+    schema
+        .get_fields()?
+        .iter()
+        .map(|field| {
+            let name = field.get_proto().get_name()?;
+            Ok((field, payload_key(name.to_str()?)))
+        })
+        .collect()
+}
+
+fn set_field(
+    stream: &str,
+    builder: &mut dynamic_struct::Builder<'_>,
+    field: Field,
+    key: &str,
+    value: &Value,
+) -> Result<(), Error> {
+    use dynamic_value::Reader;
+
+    let kind = field.get_type().which();
+    // values out of the range of their field are mismatches rather than truncated
+    let reader = match kind {
+        TypeVariant::Bool => value.as_bool().map(Reader::Bool),
+        TypeVariant::Int8 => int(value).map(Reader::Int8),
+        TypeVariant::Int16 => int(value).map(Reader::Int16),
+        TypeVariant::Int32 => int(value).map(Reader::Int32),
+        TypeVariant::Int64 => int(value).map(Reader::Int64),
+        TypeVariant::UInt8 => uint(value).map(Reader::UInt8),
+        TypeVariant::UInt16 => uint(value).map(Reader::UInt16),
+        TypeVariant::UInt32 => uint(value).map(Reader::UInt32),
+        TypeVariant::UInt64 => uint(value).map(Reader::UInt64),
+        TypeVariant::Float32 => value
+            .as_f64()
+            .filter(|v| v.abs() <= f32::MAX as f64)
+            .map(|v| Reader::Float32(v as f32)),
+        TypeVariant::Float64 => value.as_f64().map(Reader::Float64),
+        TypeVariant::Text => value.as_str().map(|v| Reader::Text(v.into())),
+        _ => None,
+    };
+    let Some(reader) = reader else {
+        return Err(type_mismatch(
+            stream,
+            key,
+            type_name(&kind),
+            json_type(value),
+        ));
+    };
+
+    Ok(builder.set(field, reader)?)
+}
+
+/// Value as a signed integer type, `None` if it doesn't fit
+fn int<T: TryFrom<i64>>(value: &Value) -> Option<T> {
+    value.as_i64()?.try_into().ok()
+}
+
+/// Value as an unsigned integer type, `None` if it doesn't fit
+fn uint<T: TryFrom<u64>>(value: &Value) -> Option<T> {
+    value.as_u64()?.try_into().ok()
+}
+
+fn type_name(kind: &TypeVariant) -> &'static str {
+    match kind {
+        TypeVariant::Bool => "Bool",
+        TypeVariant::Int8 => "Int8",
+        TypeVariant::Int16 => "Int16",
+        TypeVariant::Int32 => "Int32",
+        TypeVariant::Int64 => "Int64",
+        TypeVariant::UInt8 => "UInt8",
+        TypeVariant::UInt16 => "UInt16",
+        TypeVariant::UInt32 => "UInt32",
+        TypeVariant::UInt64 => "UInt64",
+        TypeVariant::Float32 => "Float32",
+        TypeVariant::Float64 => "Float64",
+        TypeVariant::Text => "Text",
+        _ => "a pointer or enum",
+    }
+}
+
+fn get_field(stream: &str, key: &str, value: dynamic_value::Reader<'_>) -> Result<Value, Error> {
+    use dynamic_value::Reader;
+
+    let value = match value {
+        Reader::Bool(v) => v.into(),
+        Reader::Int8(v) => v.into(),
+        Reader::Int16(v) => v.into(),
+        Reader::Int32(v) => v.into(),
+        Reader::Int64(v) => v.into(),
+        Reader::UInt8(v) => v.into(),
+        Reader::UInt16(v) => v.into(),
+        Reader::UInt32(v) => v.into(),
+        Reader::UInt64(v) => v.into(),
+        Reader::Float32(v) => (v as f64).into(),
+        Reader::Float64(v) => v.into(),
+        Reader::Text(v) => v.to_str()?.into(),
+        _ => return Err(type_mismatch(stream, key, "a scalar", "a pointer or enum")),
+    };
+
+    Ok(value)
+}
+
+fn number(value: dynamic_value::Reader<'_>) -> Option<f64> {
+    use dynamic_value::Reader;
+
+    let value = match value {
+        Reader::Int8(v) => v as f64,
+        Reader::Int16(v) => v as f64,
+        Reader::Int32(v) => v as f64,
+        Reader::Int64(v) => v as f64,
+        Reader::UInt8(v) => v as f64,
+        Reader::UInt16(v) => v as f64,
+        Reader::UInt32(v) => v as f64,
+        Reader::UInt64(v) => v as f64,
+        Reader::Float32(v) => v as f64,
+        Reader::Float64(v) => v,
+        _ => return None,
+    };
+
+    Some(value)
 }
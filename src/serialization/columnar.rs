@@ -0,0 +1,497 @@
+//! Batches transposed into a column per field. Sequences, timestamps and integers are
+//! delta encoded, floats with few decimal digits are stored as delta encoded integers
+//! and other floats contiguously, split into a stream per byte. Strings are stored
+//! through a dictionary. Similar values thus end up next to each other, for
+//! compression codecs to pick up on.
+//!
+//! A batch is its number of points, the sequence and timestamp columns and then
+//! every payload field as its name, a type tag, the points holding it and its values.
+
+use std::collections::{BTreeSet, HashMap};
+
+use serde_json::{Map, Number, Value};
+
+use crate::base::Payload;
+
+use super::Error;
+
+const FLOAT: u8 = 0;
+const INT: u8 = 1;
+const UINT: u8 = 2;
+const BOOL: u8 = 3;
+const STRING: u8 = 4;
+/// values of mixed or nested types, as json
+const JSON: u8 = 5;
+/// floats that are integers scaled down by a power of ten
+const DECIMAL: u8 = 6;
+
+/// Most decimal digits of floats stored as scaled integers
+const MAX_DIGITS: i32 = 9;
+
+const ALL_PRESENT: u8 = 0;
+const BITMAP: u8 = 1;
+
+pub fn serialize(payload: Vec<Payload>) -> Result<Vec<u8>, Error> {
+    let mut buf = vec![];
+    write_varint(&mut buf, payload.len() as u64);
+    write_deltas(&mut buf, payload.iter().map(|p| p.sequence as u64));
+    write_deltas(&mut buf, payload.iter().map(|p| p.timestamp));
+
+    let fields: BTreeSet<&str> = payload
+        .iter()
+        .filter_map(|p| p.payload.as_object())
+        .flat_map(|o| o.keys().map(|k| k.as_str()))
+        .collect();
+    write_varint(&mut buf, fields.len() as u64);
+
+    for field in fields {
+        let column: Vec<Option<&Value>> = payload.iter().map(|p| p.payload.get(field)).collect();
+        write_bytes(&mut buf, field.as_bytes());
+
+        let values: Vec<&Value> = column.iter().flatten().copied().collect();
+        if values.len() == column.len() {
+            buf.push(ALL_PRESENT);
+        } else {
+            buf.push(BITMAP);
+            write_bits(&mut buf, column.iter().map(|v| v.is_some()));
+        }
+
+        let floats = || values.iter().map(|v| v.as_f64().unwrap_or_default());
+        let mut tag = column_type(&values);
+        let digits = match tag {
+            FLOAT => decimal_digits(floats()),
+            _ => None,
+        };
+        if digits.is_some() {
+            tag = DECIMAL;
+        }
+        buf.push(tag);
+
+        match tag {
+            FLOAT => {
+                // byte streams group the sign, exponent and high mantissa bytes
+                let bytes: Vec<[u8; 8]> = floats().map(f64::to_le_bytes).collect();
+                for i in 0..8 {
+                    buf.extend(bytes.iter().map(|b| b[i]));
+                }
+            }
+            DECIMAL => {
+                let digits = digits.unwrap_or_default();
+                let scale = 10f64.powi(digits);
+                buf.push(digits as u8);
+                write_deltas(
+                    &mut buf,
+                    floats().map(|v| (v * scale).round() as i64 as u64),
+                );
+            }
+            INT => write_deltas(
+                &mut buf,
+                values.iter().map(|v| v.as_i64().unwrap_or_default() as u64),
+            ),
+            UINT => {
+                for v in values {
+                    write_varint(&mut buf, v.as_u64().unwrap_or_default());
+                }
+            }
+            BOOL => write_bits(&mut buf, values.iter().map(|v| v.as_bool() == Some(true))),
+            STRING => write_dictionary(
+                &mut buf,
+                values.iter().map(|v| v.as_str().unwrap_or_default()),
+            ),
+            _ => {
+                for v in values {
+                    write_bytes(&mut buf, &serde_json::to_vec(v)?);
+                }
+            }
+        }
+    }
+
+    Ok(buf)
+}
+
+pub fn deserialize(mut buf: &[u8]) -> Result<Vec<Payload>, Error> {
+    let buf = &mut buf;
+    let len = read_varint(buf)? as usize;
+    // every point takes at least a byte for each of its sequence and timestamp
+    if len > buf.len() {
+        return Err(Error::Columnar("more points than bytes"));
+    }
+    let sequences = read_deltas(buf, len)?;
+    let timestamps = read_deltas(buf, len)?;
+
+    let mut payloads = vec![Map::new(); len];
+    for _ in 0..read_varint(buf)? {
+        let field = String::from_utf8(read_bytes(buf)?.to_vec())
+            .map_err(|_| Error::Columnar("field name isn't utf8"))?;
+        let present = match read_u8(buf)? {
+            ALL_PRESENT => vec![true; len],
+            BITMAP => read_bits(buf, len)?,
+            _ => return Err(Error::Columnar("unknown presence")),
+        };
+        let count = present.iter().filter(|p| **p).count();
+
+        let float = |v: f64| Number::from_f64(v).map_or(Value::Null, Value::Number);
+        let values: Vec<Value> = match read_u8(buf)? {
+            FLOAT => {
+                let bytes = take(buf, count * 8)?;
+                (0..count)
+                    .map(|j| {
+                        let mut value = [0; 8];
+                        for (i, b) in value.iter_mut().enumerate() {
+                            *b = bytes[i * count + j];
+                        }
+                        float(f64::from_le_bytes(value))
+                    })
+                    .collect()
+            }
+            DECIMAL => {
+                let scale = 10f64.powi(read_u8(buf)? as i32);
+                read_deltas(buf, count)?
+                    .into_iter()
+                    .map(|v| float(v as i64 as f64 / scale))
+                    .collect()
+            }
+            INT => read_deltas(buf, count)?
+                .into_iter()
+                .map(|v| Value::from(v as i64))
+                .collect(),
+            UINT => (0..count)
+                .map(|_| Ok(read_varint(buf)?.into()))
+                .collect::<Result<_, Error>>()?,
+            BOOL => read_bits(buf, count)?
+                .into_iter()
+                .map(Value::from)
+                .collect(),
+            STRING => read_dictionary(buf, count)?,
+            JSON => (0..count)
+                .map(|_| Ok(serde_json::from_slice(read_bytes(buf)?)?))
+                .collect::<Result<_, Error>>()?,
+            _ => return Err(Error::Columnar("unknown column type")),
+        };
+
+        let holding = payloads
+            .iter_mut()
+            .zip(present)
+            .filter_map(|(payload, present)| present.then_some(payload));
+        for (payload, value) in holding.zip(values) {
+            payload.insert(field.clone(), value);
+        }
+    }
+
+    let points = payloads
+        .into_iter()
+        .zip(sequences.into_iter().zip(timestamps))
+        .map(|(payload, (sequence, timestamp))| Payload {
+            stream: String::new(),
+            sequence: sequence as u32,
+            timestamp,
+            payload: Value::Object(payload),
+        })
+        .collect();
+
+    Ok(points)
+}
+
+/// Fewest decimal digits with which every float is an integer scaled down by a power
+/// of ten, such that scaling it back down yields exactly the same float
+fn decimal_digits(values: impl Iterator<Item = f64> + Clone) -> Option<i32> {
+    (0..=MAX_DIGITS).find(|digits| {
+        let scale = 10f64.powi(*digits);
+        values.clone().all(|v| {
+            let scaled = (v * scale).round();
+            // through an integer like it is stored, which loses the sign of -0.0
+            scaled.abs() < (1u64 << 53) as f64
+                && (scaled as i64 as f64 / scale).to_bits() == v.to_bits()
+        })
+    })
+}
+
+/// Narrowest type tag that holds every value of a column without loss
+fn column_type(values: &[&Value]) -> u8 {
+    let all = |f: fn(&Value) -> bool| values.iter().all(|v| f(v));
+
+    if all(|v| v.is_f64()) {
+        FLOAT
+    } else if all(|v| v.is_i64()) {
+        INT
+    } else if all(|v| v.is_u64()) {
+        UINT
+    } else if all(|v| v.is_boolean()) {
+        BOOL
+    } else if all(|v| v.is_string()) {
+        STRING
+    } else {
+        JSON
+    }
+}
+
+fn zigzag(v: i64) -> u64 {
+    ((v << 1) ^ (v >> 63)) as u64
+}
+
+fn unzigzag(v: u64) -> i64 {
+    ((v >> 1) as i64) ^ -((v & 1) as i64)
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        buf.push(v as u8 | 0x80);
+        v >>= 7;
+    }
+    buf.push(v as u8);
+}
+
+fn read_varint(buf: &mut &[u8]) -> Result<u64, Error> {
+    let mut v = 0;
+    for shift in (0..64).step_by(7) {
+        let byte = read_u8(buf)?;
+        v |= ((byte & 0x7f) as u64) << shift;
+        if byte < 0x80 {
+            return Ok(v);
+        }
+    }
+
+    Err(Error::Columnar("varint too long"))
+}
+
+/// First value followed by the zigzag encoded difference of every value to the previous
+fn write_deltas(buf: &mut Vec<u8>, values: impl Iterator<Item = u64>) {
+    let mut previous = 0u64;
+    for v in values {
+        write_varint(buf, zigzag(v.wrapping_sub(previous) as i64));
+        previous = v;
+    }
+}
+
+fn read_deltas(buf: &mut &[u8], len: usize) -> Result<Vec<u64>, Error> {
+    let mut previous = 0u64;
+    (0..len)
+        .map(|_| {
+            previous = previous.wrapping_add(unzigzag(read_varint(buf)?) as u64);
+            Ok(previous)
+        })
+        .collect()
+}
+
+fn write_bits(buf: &mut Vec<u8>, bits: impl Iterator<Item = bool>) {
+    let mut byte = 0;
+    let mut i = 0;
+    for bit in bits {
+        byte |= (bit as u8) << (i % 8);
+        i += 1;
+        if i % 8 == 0 {
+            buf.push(byte);
+            byte = 0;
+        }
+    }
+    if i % 8 != 0 {
+        buf.push(byte);
+    }
+}
+
+fn read_bits(buf: &mut &[u8], len: usize) -> Result<Vec<bool>, Error> {
+    let bytes = take(buf, len.div_ceil(8))?;
+
+    Ok((0..len)
+        .map(|i| bytes[i / 8] & (1 << (i % 8)) != 0)
+        .collect())
+}
+
+/// Distinct strings in order of appearance, followed by the index of every value
+fn write_dictionary<'a>(buf: &mut Vec<u8>, values: impl Iterator<Item = &'a str>) {
+    let mut dictionary: HashMap<&str, u64> = HashMap::new();
+    let mut entries = vec![];
+    let indices: Vec<u64> = values
+        .map(|v| {
+            *dictionary.entry(v).or_insert_with(|| {
+                entries.push(v);
+                entries.len() as u64 - 1
+            })
+        })
+        .collect();
+
+    write_varint(buf, entries.len() as u64);
+    for entry in entries {
+        write_bytes(buf, entry.as_bytes());
+    }
+    for index in indices {
+        write_varint(buf, index);
+    }
+}
+
+fn read_dictionary(buf: &mut &[u8], len: usize) -> Result<Vec<Value>, Error> {
+    let entries = (0..read_varint(buf)?)
+        .map(|_| {
+            let entry = std::str::from_utf8(read_bytes(buf)?)
+                .map_err(|_| Error::Columnar("string isn't utf8"))?;
+            Ok(entry.to_owned())
+        })
+        .collect::<Result<Vec<String>, Error>>()?;
+
+    (0..len)
+        .map(|_| {
+            let entry = entries
+                .get(read_varint(buf)? as usize)
+                .ok_or(Error::Columnar("string index out of the dictionary"))?;
+            Ok(Value::from(entry.as_str()))
+        })
+        .collect()
+}
+
+fn write_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+    write_varint(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+fn read_bytes<'a>(buf: &mut &'a [u8]) -> Result<&'a [u8], Error> {
+    let len = read_varint(buf)? as usize;
+    take(buf, len)
+}
+
+fn read_u8(buf: &mut &[u8]) -> Result<u8, Error> {
+    Ok(take(buf, 1)?[0])
+}
+
+fn take<'a>(buf: &mut &'a [u8], len: usize) -> Result<&'a [u8], Error> {
+    if buf.len() < len {
+        return Err(Error::Columnar("unexpected end of batch"));
+    }
+    let (taken, rest) = buf.split_at(len);
+    *buf = rest;
+
+    Ok(taken)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn point(sequence: u32, timestamp: u64, payload: Value) -> Payload {
+        Payload {
+            stream: String::new(),
+            sequence,
+            timestamp,
+            payload,
+        }
+    }
+
+    fn round_trip(points: Vec<Payload>) -> Vec<Payload> {
+        deserialize(&serialize(points).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn empty_batch() {
+        assert_eq!(round_trip(vec![]), vec![]);
+    }
+
+    #[test]
+    fn fields_missing_from_some_points() {
+        let points = vec![
+            point(1, 100, json!({"a": 1.5, "b": "x"})),
+            point(2, 200, json!({"b": "y"})),
+            point(3, 300, json!({})),
+            point(4, 400, json!({"a": 2.25, "c": true})),
+        ];
+
+        assert_eq!(round_trip(points.clone()), points);
+    }
+
+    #[test]
+    fn mixed_types_fall_back_to_json() {
+        let points = vec![
+            point(1, 100, json!({"a": 1, "b": 1.5})),
+            point(2, 200, json!({"a": "x", "b": null})),
+            point(3, 300, json!({"a": true, "b": [1, 2]})),
+            point(4, 400, json!({"a": {"nested": 2.5}, "b": -3})),
+        ];
+
+        assert_eq!(round_trip(points.clone()), points);
+    }
+
+    #[test]
+    fn integer_extremes() {
+        let points = vec![
+            point(u32::MAX, u64::MAX, json!({"i": i64::MIN, "u": u64::MAX})),
+            point(0, 0, json!({"i": i64::MAX, "u": 0})),
+            point(u32::MAX, u64::MAX, json!({"i": i64::MIN, "u": u64::MAX})),
+        ];
+
+        assert_eq!(round_trip(points.clone()), points);
+    }
+
+    #[test]
+    fn signed_integers_beyond_i64_fall_back_to_json() {
+        let points = vec![
+            point(1, 100, json!({"a": -1})),
+            point(2, 200, json!({"a": u64::MAX})),
+        ];
+
+        assert_eq!(round_trip(points.clone()), points);
+    }
+
+    #[test]
+    fn float_extremes() {
+        let points = vec![
+            point(1, 100, json!({"a": -0.0, "b": f64::MAX})),
+            point(2, 200, json!({"a": 0.1, "b": f64::MIN_POSITIVE})),
+            point(3, 300, json!({"a": 0.0, "b": -f64::MAX})),
+        ];
+
+        let decoded = round_trip(points.clone());
+        assert_eq!(decoded, points);
+        // -0.0 equals 0.0, its sign is only seen in its bits
+        assert!(decoded[0].payload["a"].as_f64().unwrap().is_sign_negative());
+    }
+
+    #[test]
+    fn nan_is_null() {
+        // json has no NaN, serde_json turns it into null before it is serialized
+        let points = vec![
+            point(1, 100, json!({"a": f64::NAN})),
+            point(2, 200, json!({"a": 1.5})),
+        ];
+        assert_eq!(points[0].payload["a"], Value::Null);
+
+        assert_eq!(round_trip(points.clone()), points);
+    }
+
+    #[test]
+    fn nan_read_from_a_float_column_is_null() {
+        let mut serialized = serialize(vec![point(1, 100, json!({"a": 1.0e300}))]).unwrap();
+        let len = serialized.len();
+        serialized[len - 8..].copy_from_slice(&f64::NAN.to_le_bytes());
+
+        let points = deserialize(&serialized).unwrap();
+        assert_eq!(points[0].payload["a"], Value::Null);
+    }
+
+    #[test]
+    fn truncated_batches_are_errors() {
+        let points = vec![
+            point(
+                1,
+                100,
+                json!({"f": 1.0e300, "d": 2.5, "i": -4, "s": "x", "j": [1]}),
+            ),
+            point(2, 200, json!({"u": u64::MAX, "b": true, "j": "y"})),
+        ];
+        let serialized = serialize(points).unwrap();
+
+        for len in 0..serialized.len() {
+            match deserialize(&serialized[..len]) {
+                Err(Error::Columnar(_)) => {}
+                result => panic!("batch truncated to {} bytes read as {:?}", len, result),
+            }
+        }
+    }
+
+    #[test]
+    fn point_counts_beyond_the_batch_are_errors() {
+        let mut serialized = vec![];
+        write_varint(&mut serialized, u64::MAX);
+
+        assert!(matches!(deserialize(&serialized), Err(Error::Columnar(_))));
+    }
+}
@@ -0,0 +1,403 @@
+use std::borrow::Cow;
+
+use flatbuffers::{FlatBufferBuilder, WIPOffset};
+use serde_json::{Map, Value};
+
+use crate::base::Payload;
+
+use super::{json_type, type_mismatch, Error, FieldRead};
+
+#[allow(unused_imports, dead_code, clippy::all)]
+mod generated {
+    include!(concat!(env!("OUT_DIR"), "/test_generated.rs"));
+}
+
+use generated::zerde;
+
+/// Writes [`STREAMS`], [`serialize`], [`deserialize`] and [`read_fields`] for the list
+/// tables of streams and the fields of their point tables. Points are built and read
+/// through the code flatc generated from `test.fbs`, which fails to compile unless
+/// the fields match the generated `<Point>Args` and accessors
+macro_rules! tables {
+    ($($stream:literal => $List:ident, $ListArgs:ident, $Point:ident, $PointArgs:ident {
+        $($field:ident: $ty:ty),* $(,)?
+    }),* $(,)?) => {
+        /// Streams with a list table in `test.fbs`
+        pub const STREAMS: &[&str] = &[$($stream),*];
+
+        pub fn serialize(payload: Vec<Payload>, stream: &str) -> Result<Vec<u8>, Error> {
+            let mut builder;
+            match stream {
+                $($stream => {
+                    let fields = [$(stringify!($field)),*].len();
+                    builder = FlatBufferBuilder::with_capacity(payload.len() * fields * 8);
+
+                    let mut points = Vec::with_capacity(payload.len());
+                    for point in payload.iter() {
+                        let args = zerde::$PointArgs {
+                            $($field: arg::<$ty>(&mut builder, stream, stringify!($field), point)?,)*
+                        };
+                        points.push(zerde::$Point::create(&mut builder, &args));
+                    }
+                    let messages = Some(builder.create_vector(&points));
+                    let list = zerde::$List::create(&mut builder, &zerde::$ListArgs { messages });
+                    builder.finish_minimal(list);
+                })*
+                stream => return Err(Error::UnknownStream(stream.to_owned())),
+            }
+
+            Ok(builder.finished_data().to_vec())
+        }
+
+        pub fn deserialize(payload: &[u8], stream: &str) -> Result<Vec<Payload>, Error> {
+            match stream {
+                $($stream => {
+                    let list = flatbuffers::root::<zerde::$List>(payload)?;
+                    let points = list.messages().into_iter().flatten().map(|point| {
+                        let mut read = Payload::default();
+                        let mut payload = Map::new();
+                        $(insert(&mut read, &mut payload, stringify!($field), point.$field());)*
+                        read.payload = Value::Object(payload);
+
+                        read
+                    });
+
+                    Ok(points.collect())
+                })*
+                stream => Err(Error::UnknownStream(stream.to_owned())),
+            }
+        }
+
+        /// Folds fields of the points straight from the verified buffer, leaving every
+        /// other field unread
+        pub fn read_fields(
+            payload: &[u8],
+            stream: &str,
+            reads: &[FieldRead],
+        ) -> Result<Vec<f64>, Error> {
+            match stream {
+                $($stream => {
+                    let list = flatbuffers::root::<zerde::$List>(payload)?;
+                    // accessor of every read, fields the table doesn't have folding to empty
+                    let accessors: Vec<Option<Accessor<zerde::$Point<'_>>>> = reads
+                        .iter()
+                        .map(|read| -> Option<Accessor<zerde::$Point<'_>>> {
+                            match read.field.as_str() {
+                                $(stringify!($field) => Some(|point| point.$field().number()),)*
+                                _ => None,
+                            }
+                        })
+                        .collect();
+
+                    Ok(fold(list.messages().into_iter().flatten(), reads, &accessors))
+                })*
+                stream => Err(Error::UnknownStream(stream.to_owned())),
+            }
+        }
+    };
+}
+
+tables! {
+    "gps" => GpsList, GpsListArgs, Gps, GpsArgs {
+        longitude: f64,
+        latitude: f64,
+        timestamp: u64,
+        sequence: i32,
+    },
+    "imu" => ImuList, ImuListArgs, Imu, ImuArgs {
+        timestamp: u64,
+        sequence: u32,
+        ax: f64,
+        ay: f64,
+        az: f64,
+        pitch: f64,
+        roll: f64,
+        yaw: f64,
+        magx: f64,
+        magy: f64,
+        magz: f64,
+    },
+    "peripherals" => PeripheralsList, PeripheralsListArgs, Peripherals, PeripheralsArgs {
+        timestamp: u64,
+        sequence: u32,
+        gps: str,
+        gsm: str,
+        imu: str,
+        left_indicator: str,
+        right_indicator: str,
+        headlamp: str,
+        horn: str,
+        left_brake: str,
+        right_brake: str,
+    },
+    "bms" => BmsList, BmsListArgs, Bms, BmsArgs {
+        sequence: i32,
+        timestamp: u64,
+        periodicity_ms: i32,
+        mosfet_temperature: f64,
+        ambient_temperature: f64,
+        mosfet_status: i32,
+        cell_voltage_count: i32,
+        cell_voltage_1: f64,
+        cell_voltage_2: f64,
+        cell_voltage_3: f64,
+        cell_voltage_4: f64,
+        cell_voltage_5: f64,
+        cell_voltage_6: f64,
+        cell_voltage_7: f64,
+        cell_voltage_8: f64,
+        cell_voltage_9: f64,
+        cell_voltage_10: f64,
+        cell_voltage_11: f64,
+        cell_voltage_12: f64,
+        cell_voltage_13: f64,
+        cell_voltage_14: f64,
+        cell_voltage_15: f64,
+        cell_voltage_16: f64,
+        cell_thermistor_count: i32,
+        cell_temp_1: f64,
+        cell_temp_2: f64,
+        cell_temp_3: f64,
+        cell_temp_4: f64,
+        cell_temp_5: f64,
+        cell_temp_6: f64,
+        cell_temp_7: f64,
+        cell_temp_8: f64,
+        cell_balancing_status: i32,
+        pack_voltage: f64,
+        pack_current: f64,
+        pack_soc: f64,
+        pack_soh: f64,
+        pack_sop: f64,
+        pack_cycle_count: i64,
+        pack_available_energy: i64,
+        pack_consumed_energy: i64,
+        pack_fault: i32,
+        pack_status: i32,
+    },
+    "motor" => MotorList, MotorListArgs, Motor, MotorArgs {
+        timestamp: u64,
+        sequence: u32,
+        temperature1: f64,
+        temperature2: f64,
+        temperature3: f64,
+        voltage: f64,
+        current: f64,
+        rpm: u32,
+    },
+    "can" => CanList, CanListArgs, Can, CanArgs {
+        timestamp: u64,
+        sequence: i32,
+        data: u64,
+    },
+    "shadow" => ShadowList, ShadowListArgs, Shadow, ShadowArgs {
+        mode: str,
+        status: str,
+        firmware_version: str,
+        config_version: str,
+        distance_travelled: i64,
+        range: i64,
+        soc: f64,
+        sequence: i32,
+        timestamp: u64,
+    },
+}
+
+/// Type of a field of a point table, as set in its generated `<Point>Args`
+trait FieldArg<'fbb> {
+    /// Name of the type in the schema
+    const NAME: &'static str;
+
+    type Arg;
+
+    /// `None` if the value doesn't fit the type
+    fn arg(builder: &mut FlatBufferBuilder<'fbb>, value: &Value) -> Option<Self::Arg>;
+}
+
+/// Value of a field of a point table, as returned by its generated accessor
+trait FieldValue {
+    fn json(self) -> Value;
+
+    /// `None` if the field isn't numeric
+    fn number(self) -> Option<f64>;
+}
+
+macro_rules! scalar {
+    ($ty:ty, $name:literal, $json:ident) => {
+        impl FieldArg<'_> for $ty {
+            const NAME: &'static str = $name;
+
+            type Arg = $ty;
+
+            fn arg(_: &mut FlatBufferBuilder<'_>, value: &Value) -> Option<$ty> {
+                value.$json().and_then(|v| <$ty>::try_from(v).ok())
+            }
+        }
+
+        impl FieldValue for $ty {
+            fn json(self) -> Value {
+                Value::from(self)
+            }
+
+            fn number(self) -> Option<f64> {
+                Some(self as f64)
+            }
+        }
+    };
+}
+
+scalar!(i32, "int", as_i64);
+scalar!(i64, "long", as_i64);
+scalar!(u32, "uint", as_u64);
+scalar!(u64, "ulong", as_u64);
+scalar!(f64, "double", as_f64);
+
+impl<'fbb> FieldArg<'fbb> for str {
+    const NAME: &'static str = "string";
+
+    type Arg = Option<WIPOffset<&'fbb str>>;
+
+    fn arg(builder: &mut FlatBufferBuilder<'fbb>, value: &Value) -> Option<Self::Arg> {
+        value.as_str().map(|v| Some(builder.create_string(v)))
+    }
+}
+
+impl FieldValue for Option<&str> {
+    fn json(self) -> Value {
+        Value::from(self.unwrap_or_default())
+    }
+
+    fn number(self) -> Option<f64> {
+        None
+    }
+}
+
+/// Value of a field of a point for its generated `<Point>Args`
+fn arg<'fbb, T: FieldArg<'fbb> + ?Sized>(
+    builder: &mut FlatBufferBuilder<'fbb>,
+    stream: &str,
+    name: &str,
+    point: &Payload,
+) -> Result<T::Arg, Error> {
+    let value = match name {
+        "sequence" => Cow::Owned(Value::from(point.sequence)),
+        "timestamp" => Cow::Owned(Value::from(point.timestamp)),
+        name => match point.payload.get(name) {
+            Some(value) => Cow::Borrowed(value),
+            None => {
+                return Err(Error::MissingField {
+                    stream: stream.to_owned(),
+                    field: name.to_owned(),
+                })
+            }
+        },
+    };
+
+    T::arg(builder, &value).ok_or_else(|| type_mismatch(stream, name, T::NAME, json_type(&value)))
+}
+
+/// Sets a field read from a point table on its point
+fn insert(
+    read: &mut Payload,
+    payload: &mut Map<String, Value>,
+    name: &str,
+    value: impl FieldValue,
+) {
+    let value = value.json();
+    let as_u64 = || value.as_u64().or_else(|| value.as_i64().map(|v| v as u64));
+    match name {
+        "sequence" => read.sequence = as_u64().unwrap_or_default() as u32,
+        "timestamp" => read.timestamp = as_u64().unwrap_or_default(),
+        name => {
+            payload.insert(name.to_owned(), value);
+        }
+    }
+}
+
+/// Generated accessor of a numeric field of a point table
+type Accessor<P> = fn(&P) -> Option<f64>;
+
+fn fold<P>(
+    points: impl Iterator<Item = P>,
+    reads: &[FieldRead],
+    accessors: &[Option<Accessor<P>>],
+) -> Vec<f64> {
+    let mut folded: Vec<f64> = reads.iter().map(|r| r.fold.empty()).collect();
+    for point in points {
+        for ((read, accessor), folded) in reads.iter().zip(accessors).zip(folded.iter_mut()) {
+            if let Some(value) = accessor.and_then(|accessor| accessor(&point)) {
+                *folded = read.fold.step(*folded, value);
+            }
+        }
+    }
+
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulator::golden_points;
+
+    #[test]
+    fn every_stream_round_trips() {
+        let golden = golden_points();
+        assert_eq!(golden.len(), STREAMS.len());
+
+        for (stream, point) in golden {
+            let serialized = serialize(vec![point.clone(), point.clone()], &stream).unwrap();
+            assert_eq!(
+                deserialize(&serialized, &stream).unwrap(),
+                [point.clone(), point]
+            );
+        }
+    }
+
+    #[test]
+    fn fields_left_out_by_generated_builders_read_as_defaults() {
+        let mut builder = FlatBufferBuilder::new();
+        let mode = builder.create_string("idle");
+        let point = zerde::Shadow::create(
+            &mut builder,
+            &zerde::ShadowArgs {
+                mode: Some(mode),
+                sequence: 3,
+                soc: 0.5,
+                ..Default::default()
+            },
+        );
+        let messages = Some(builder.create_vector(&[point]));
+        let list = zerde::ShadowList::create(&mut builder, &zerde::ShadowListArgs { messages });
+        builder.finish_minimal(list);
+
+        let points = deserialize(builder.finished_data(), "shadow").unwrap();
+        let expected = Payload {
+            sequence: 3,
+            payload: serde_json::json!({
+                "mode": "idle",
+                "status": "",
+                "firmware_version": "",
+                "config_version": "",
+                "distance_travelled": 0,
+                "range": 0,
+                "soc": 0.5,
+            }),
+            ..Default::default()
+        };
+        assert_eq!(points, [expected]);
+    }
+
+    #[test]
+    fn values_out_of_range_of_their_field_are_type_mismatches() {
+        let point = Payload {
+            sequence: u32::MAX,
+            payload: serde_json::json!({"longitude": 1.0, "latitude": 2.0}),
+            ..Default::default()
+        };
+
+        let Err(Error::TypeMismatch { field, .. }) = serialize(vec![point], "gps") else {
+            panic!("sequence beyond an int serialized");
+        };
+        assert_eq!(field, "sequence");
+    }
+}
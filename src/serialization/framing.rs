@@ -0,0 +1,372 @@
+//! Length-delimited framing of serialized batches, for many batches of any format to
+//! be written into one file or socket and read back. A frame is
+//!
+//! | bytes    | content                                     |
+//! |----------|---------------------------------------------|
+//! | 1        | length of the stream name                   |
+//! | as above | stream name, utf-8                          |
+//! | 4        | length of the batch, unsigned little-endian |
+//! | as above | batch as serialized by its format           |
+//!
+//! Frames don't name their format, every frame of a file is read with the same one.
+
+use std::io::{ErrorKind, Read, Write};
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::base::Payload;
+
+use super::{Error, Serializer};
+
+/// A serialized batch and the stream it belongs to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub stream: String,
+    pub payload: Vec<u8>,
+}
+
+impl Frame {
+    /// Points of the batch, `serializer` being the format it was written with
+    pub fn deserialize(&self, serializer: &dyn Serializer) -> Result<Vec<Payload>, Error> {
+        serializer.deserialize(&self.stream, &self.payload)
+    }
+}
+
+/// Writes batches as frames into a [`Write`] or, through the `_async` methods, an
+/// [`AsyncWrite`] sink. Frames aren't buffered, wrap files and sockets in a buffered
+/// writer
+pub struct FrameWriter<W> {
+    inner: W,
+}
+
+impl<W> FrameWriter<W> {
+    pub fn new(inner: W) -> FrameWriter<W> {
+        FrameWriter { inner }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> FrameWriter<W> {
+    /// Serializes a batch and writes it as a frame, returns the length of the frame
+    pub fn write_batch(
+        &mut self,
+        serializer: &dyn Serializer,
+        stream: &str,
+        payload: Vec<Payload>,
+    ) -> Result<usize, Error> {
+        let serialized = serializer.serialize(stream, payload)?;
+        self.write_frame(stream, &serialized)
+    }
+
+    /// Writes an already serialized batch as a frame, returns the length of the frame
+    pub fn write_frame(&mut self, stream: &str, payload: &[u8]) -> Result<usize, Error> {
+        let header = header(stream, payload)?;
+        self.inner.write_all(&header)?;
+        self.inner.write_all(payload)?;
+
+        Ok(header.len() + payload.len())
+    }
+
+    pub fn flush(&mut self) -> Result<(), Error> {
+        Ok(self.inner.flush()?)
+    }
+}
+
+impl<W: AsyncWrite + Unpin> FrameWriter<W> {
+    pub async fn write_batch_async(
+        &mut self,
+        serializer: &dyn Serializer,
+        stream: &str,
+        payload: Vec<Payload>,
+    ) -> Result<usize, Error> {
+        let serialized = serializer.serialize(stream, payload)?;
+        self.write_frame_async(stream, &serialized).await
+    }
+
+    pub async fn write_frame_async(
+        &mut self,
+        stream: &str,
+        payload: &[u8],
+    ) -> Result<usize, Error> {
+        let header = header(stream, payload)?;
+        self.inner.write_all(&header).await?;
+        self.inner.write_all(payload).await?;
+
+        Ok(header.len() + payload.len())
+    }
+
+    pub async fn flush_async(&mut self) -> Result<(), Error> {
+        Ok(self.inner.flush().await?)
+    }
+}
+
+/// Reads frames written by [`FrameWriter`] from a [`Read`] or, through the `_async`
+/// methods, an [`AsyncRead`] source. Reading ends cleanly only between frames, a
+/// source ending within one is an error
+pub struct FrameReader<R> {
+    inner: R,
+}
+
+impl<R> FrameReader<R> {
+    pub fn new(inner: R) -> FrameReader<R> {
+        FrameReader { inner }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> FrameReader<R> {
+    /// Next frame, `None` once the source has ended
+    pub fn read_frame(&mut self) -> Result<Option<Frame>, Error> {
+        let mut len = [0; 1];
+        match self.inner.read_exact(&mut len) {
+            Ok(_) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+        let mut stream = vec![0; len[0] as usize];
+        self.inner.read_exact(&mut stream)?;
+
+        let mut len = [0; 4];
+        self.inner.read_exact(&mut len)?;
+        let len = u32::from_le_bytes(len);
+        let mut payload = vec![];
+        self.inner
+            .by_ref()
+            .take(len as u64)
+            .read_to_end(&mut payload)?;
+        check_len(&payload, len)?;
+
+        frame(stream, payload).map(Some)
+    }
+
+    /// Points of the next frame and its stream, `None` once the source has ended
+    pub fn read_batch(
+        &mut self,
+        serializer: &dyn Serializer,
+    ) -> Result<Option<(String, Vec<Payload>)>, Error> {
+        let Some(frame) = self.read_frame()? else {
+            return Ok(None);
+        };
+        let points = frame.deserialize(serializer)?;
+
+        Ok(Some((frame.stream, points)))
+    }
+}
+
+impl<R: AsyncRead + Unpin> FrameReader<R> {
+    pub async fn read_frame_async(&mut self) -> Result<Option<Frame>, Error> {
+        let mut len = [0; 1];
+        match self.inner.read_exact(&mut len).await {
+            Ok(_) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+        let mut stream = vec![0; len[0] as usize];
+        self.inner.read_exact(&mut stream).await?;
+
+        let mut len = [0; 4];
+        self.inner.read_exact(&mut len).await?;
+        let len = u32::from_le_bytes(len);
+        let mut payload = vec![];
+        (&mut self.inner)
+            .take(len as u64)
+            .read_to_end(&mut payload)
+            .await?;
+        check_len(&payload, len)?;
+
+        frame(stream, payload).map(Some)
+    }
+
+    pub async fn read_batch_async(
+        &mut self,
+        serializer: &dyn Serializer,
+    ) -> Result<Option<(String, Vec<Payload>)>, Error> {
+        let Some(frame) = self.read_frame_async().await? else {
+            return Ok(None);
+        };
+        let points = frame.deserialize(serializer)?;
+
+        Ok(Some((frame.stream, points)))
+    }
+}
+
+impl<R: Read> Iterator for FrameReader<R> {
+    type Item = Result<Frame, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_frame().transpose()
+    }
+}
+
+fn header(stream: &str, payload: &[u8]) -> Result<Vec<u8>, Error> {
+    let Ok(stream_len) = u8::try_from(stream.len()) else {
+        return Err(Error::Frame("stream name longer than 255 bytes"));
+    };
+    let Ok(payload_len) = u32::try_from(payload.len()) else {
+        return Err(Error::Frame("batch longer than 4 GiB"));
+    };
+
+    let mut header = Vec::with_capacity(1 + stream.len() + 4);
+    header.push(stream_len);
+    header.extend_from_slice(stream.as_bytes());
+    header.extend_from_slice(&payload_len.to_le_bytes());
+
+    Ok(header)
+}
+
+/// Checks that the source didn't end within the batch of a frame. Batches are read up
+/// to their length rather than into a buffer of it, for a corrupt length not to
+/// allocate gigabytes ahead of a source that ends long before
+fn check_len(payload: &[u8], len: u32) -> Result<(), Error> {
+    if payload.len() != len as usize {
+        return Err(std::io::Error::from(ErrorKind::UnexpectedEof).into());
+    }
+
+    Ok(())
+}
+
+fn frame(stream: Vec<u8>, payload: Vec<u8>) -> Result<Frame, Error> {
+    let Ok(stream) = String::from_utf8(stream) else {
+        return Err(Error::Frame("stream name isn't utf-8"));
+    };
+
+    Ok(Frame { stream, payload })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use serde_json::json;
+
+    use super::super::Json;
+    use super::*;
+
+    fn point(sequence: u32) -> Payload {
+        Payload {
+            stream: String::new(),
+            sequence,
+            timestamp: 1000 + sequence as u64,
+            payload: json!({"voltage": 48.5, "mode": "eco"}),
+        }
+    }
+
+    fn batches() -> Vec<(&'static str, Vec<Payload>)> {
+        vec![
+            ("motor", vec![point(1), point(2)]),
+            ("shadow", vec![]),
+            ("can", vec![point(3)]),
+        ]
+    }
+
+    #[test]
+    fn frames_round_trip() {
+        let mut writer = FrameWriter::new(Cursor::new(vec![]));
+        let mut written = 0;
+        for (stream, points) in batches() {
+            written += writer.write_batch(&Json, stream, points).unwrap();
+        }
+        writer.flush().unwrap();
+        let buf = writer.into_inner().into_inner();
+        assert_eq!(buf.len(), written);
+
+        let mut reader = FrameReader::new(Cursor::new(buf));
+        for (stream, points) in batches() {
+            assert_eq!(
+                reader.read_batch(&Json).unwrap(),
+                Some((stream.to_owned(), points))
+            );
+        }
+        assert_eq!(reader.read_batch(&Json).unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn frames_round_trip_async() {
+        let mut writer = FrameWriter::new(Cursor::new(vec![]));
+        for (stream, points) in batches() {
+            writer
+                .write_batch_async(&Json, stream, points)
+                .await
+                .unwrap();
+        }
+        writer.flush_async().await.unwrap();
+        let buf = writer.into_inner().into_inner();
+
+        let mut reader = FrameReader::new(Cursor::new(buf));
+        for (stream, points) in batches() {
+            assert_eq!(
+                reader.read_batch_async(&Json).await.unwrap(),
+                Some((stream.to_owned(), points))
+            );
+        }
+        assert_eq!(reader.read_batch_async(&Json).await.unwrap(), None);
+    }
+
+    #[test]
+    fn sources_end_only_between_frames() {
+        let mut writer = FrameWriter::new(vec![]);
+        let first = writer.write_frame("imu", b"first").unwrap();
+        writer.write_frame("imu", b"second").unwrap();
+        let buf = writer.into_inner();
+
+        // every length that cuts into the second frame, from its header to its batch
+        for len in first + 1..buf.len() {
+            let mut reader = FrameReader::new(&buf[..len]);
+            assert!(reader.read_frame().unwrap().is_some());
+            match reader.read_frame() {
+                Err(Error::Io(e)) if e.kind() == ErrorKind::UnexpectedEof => {}
+                result => panic!("frame cut at {} bytes read as {:?}", len, result),
+            }
+        }
+
+        let frames: Vec<Frame> = FrameReader::new(&buf[..first])
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            frames,
+            [Frame {
+                stream: "imu".to_owned(),
+                payload: b"first".to_vec(),
+            }]
+        );
+        assert!(FrameReader::new(&[][..]).read_frame().unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn lengths_past_the_end_of_the_source_are_errors() {
+        let mut buf = vec![3];
+        buf.extend_from_slice(b"imu");
+        buf.extend_from_slice(&u32::MAX.to_le_bytes());
+        buf.extend_from_slice(b"short");
+
+        match FrameReader::new(&buf[..]).read_frame() {
+            Err(Error::Io(e)) if e.kind() == ErrorKind::UnexpectedEof => {}
+            result => panic!("frame longer than its source read as {:?}", result),
+        }
+        match FrameReader::new(&buf[..]).read_frame_async().await {
+            Err(Error::Io(e)) if e.kind() == ErrorKind::UnexpectedEof => {}
+            result => panic!("frame longer than its source read as {:?}", result),
+        }
+    }
+
+    #[test]
+    fn stream_names_longer_than_255_bytes_are_errors() {
+        let mut writer = FrameWriter::new(vec![]);
+        let stream = "s".repeat(256);
+
+        assert!(matches!(
+            writer.write_frame(&stream, b"batch"),
+            Err(Error::Frame(_))
+        ));
+        assert!(writer.into_inner().is_empty());
+        assert!(FrameWriter::new(vec![])
+            .write_frame(&"s".repeat(255), b"batch")
+            .is_ok());
+    }
+}
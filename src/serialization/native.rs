@@ -0,0 +1,267 @@
+//! Formats that aren't self describing, which encode the simulator's typed points rather
+//! than their json payload. Field names and types are left out of the encoding, as
+//! both ends share the definitions of the points
+
+use rkyv::{rancor, util::AlignedVec, vec::ArchivedVec, Archive};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::base::Payload;
+use crate::simulator::{
+    ArchivedBms, ArchivedCan, ArchivedDeviceShadow, ArchivedImu, ArchivedLocation, ArchivedMotor,
+    ArchivedPeripheral, Bms, Can, DeviceShadow, Imu, Location, Motor, Peripheral,
+};
+
+use super::{Error, FieldRead};
+
+/// Alignment of the widest field of an archive
+const ARCHIVE_ALIGN: usize = 16;
+
+/// Streams with a point type
+pub const STREAMS: &[&str] = &["gps", "imu", "peripherals", "bms", "motor", "can", "shadow"];
+
+/// Point with the payload of its stream as a type of its own
+#[derive(Serialize, Deserialize, rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct Point<T> {
+    sequence: u32,
+    timestamp: u64,
+    payload: T,
+}
+
+/// Payload type as archived by rkyv, whose numeric fields are read in place
+pub trait ArchivedFields {
+    /// Accessor of a numeric field, `None` if the payload has no such field or it
+    /// isn't numeric
+    fn accessor(field: &str) -> Option<fn(&Self) -> f64>;
+}
+
+/// Field of an archived point to be folded
+enum ArchivedField<P> {
+    Sequence,
+    Timestamp,
+    Payload(fn(&P) -> f64),
+}
+
+/// Reads the named numeric fields of archived point types in place
+macro_rules! archived_fields {
+    ($($Archived:ident { $($field:ident),* $(,)? }),* $(,)?) => {$(
+        impl ArchivedFields for $Archived {
+            fn accessor(field: &str) -> Option<fn(&Self) -> f64> {
+                match field {
+                    $(stringify!($field) => Some(|p| p.$field.to_native() as f64),)*
+                    _ => None,
+                }
+            }
+        }
+    )*};
+}
+
+archived_fields! {
+    ArchivedLocation { latitude, longitude },
+    ArchivedImu { ax, ay, az, pitch, roll, yaw, magx, magy, magz },
+    ArchivedMotor { temperature1, temperature2, temperature3, voltage, current, rpm },
+    ArchivedCan { data },
+    ArchivedDeviceShadow { distance_travelled, range, soc },
+    ArchivedBms {
+        periodicity_ms,
+        mosfet_temperature,
+        ambient_temperature,
+        mosfet_status,
+        cell_voltage_count,
+        cell_voltage_1,
+        cell_voltage_2,
+        cell_voltage_3,
+        cell_voltage_4,
+        cell_voltage_5,
+        cell_voltage_6,
+        cell_voltage_7,
+        cell_voltage_8,
+        cell_voltage_9,
+        cell_voltage_10,
+        cell_voltage_11,
+        cell_voltage_12,
+        cell_voltage_13,
+        cell_voltage_14,
+        cell_voltage_15,
+        cell_voltage_16,
+        cell_thermistor_count,
+        cell_temp_1,
+        cell_temp_2,
+        cell_temp_3,
+        cell_temp_4,
+        cell_temp_5,
+        cell_temp_6,
+        cell_temp_7,
+        cell_temp_8,
+        cell_balancing_status,
+        pack_voltage,
+        pack_current,
+        pack_soc,
+        pack_soh,
+        pack_sop,
+        pack_cycle_count,
+        pack_available_energy,
+        pack_consumed_energy,
+        pack_fault,
+        pack_status,
+    },
+}
+
+/// Peripherals only hold strings
+impl ArchivedFields for ArchivedPeripheral {
+    fn accessor(_: &str) -> Option<fn(&Self) -> f64> {
+        None
+    }
+}
+
+/// Evaluates `$body` with `$T` being the payload type of a stream
+macro_rules! with_payload_type {
+    ($stream:expr, $T:ident => $body:expr) => {
+        match $stream {
+            "gps" => {
+                type $T = Location;
+                $body
+            }
+            "imu" => {
+                type $T = Imu;
+                $body
+            }
+            "peripherals" => {
+                type $T = Peripheral;
+                $body
+            }
+            "bms" => {
+                type $T = Bms;
+                $body
+            }
+            "motor" => {
+                type $T = Motor;
+                $body
+            }
+            "can" => {
+                type $T = Can;
+                $body
+            }
+            "shadow" => {
+                type $T = DeviceShadow;
+                $body
+            }
+            stream => Err(Error::UnknownStream(stream.to_owned())),
+        }
+    };
+}
+
+pub fn serialize_bincode(payload: Vec<Payload>, stream: &str) -> Result<Vec<u8>, Error> {
+    with_payload_type!(stream, T => Ok(bincode::serialize(&points::<T>(&payload)?)?))
+}
+
+pub fn deserialize_bincode(payload: &[u8], stream: &str) -> Result<Vec<Payload>, Error> {
+    with_payload_type!(stream, T => payloads(bincode::deserialize::<Vec<Point<T>>>(payload)?))
+}
+
+pub fn serialize_postcard(payload: Vec<Payload>, stream: &str) -> Result<Vec<u8>, Error> {
+    with_payload_type!(stream, T => Ok(postcard::to_allocvec(&points::<T>(&payload)?)?))
+}
+
+pub fn deserialize_postcard(payload: &[u8], stream: &str) -> Result<Vec<Payload>, Error> {
+    with_payload_type!(stream, T => payloads(postcard::from_bytes::<Vec<Point<T>>>(payload)?))
+}
+
+pub fn serialize_rkyv(payload: Vec<Payload>, stream: &str) -> Result<Vec<u8>, Error> {
+    with_payload_type!(stream, T => {
+        let archive = rkyv::to_bytes::<rancor::Error>(&points::<T>(&payload)?)?;
+        Ok(archive.into_vec())
+    })
+}
+
+pub fn deserialize_rkyv(payload: &[u8], stream: &str) -> Result<Vec<Payload>, Error> {
+    aligned(payload, |archive| {
+        with_payload_type!(stream, T => {
+            payloads(rkyv::from_bytes::<Vec<Point<T>>, rancor::Error>(archive)?)
+        })
+    })
+}
+
+/// Folds fields of the points straight from the validated archive, leaving every
+/// other field unread
+pub fn read_fields_rkyv(
+    payload: &[u8],
+    stream: &str,
+    reads: &[FieldRead],
+) -> Result<Vec<f64>, Error> {
+    aligned(payload, |archive| {
+        with_payload_type!(stream, T => {
+            let points = rkyv::access::<ArchivedVec<ArchivedPoint<T>>, rancor::Error>(archive)?;
+            Ok(fold_archived::<T>(points, reads))
+        })
+    })
+}
+
+/// Runs `f` on an archive, which is read in place and thus needs to be aligned to
+/// its widest field. The payload is only copied into an aligned buffer if it isn't
+fn aligned<R>(payload: &[u8], f: impl FnOnce(&[u8]) -> R) -> R {
+    if (payload.as_ptr() as usize).is_multiple_of(ARCHIVE_ALIGN) {
+        return f(payload);
+    }
+
+    let mut archive = AlignedVec::<ARCHIVE_ALIGN>::with_capacity(payload.len());
+    archive.extend_from_slice(payload);
+    f(&archive)
+}
+
+fn fold_archived<T>(points: &[ArchivedPoint<T>], reads: &[FieldRead]) -> Vec<f64>
+where
+    T: Archive,
+    T::Archived: ArchivedFields,
+{
+    // field of every read, fields the payload doesn't have folding to empty
+    let fields: Vec<Option<ArchivedField<T::Archived>>> = reads
+        .iter()
+        .map(|r| match r.field.as_str() {
+            "sequence" => Some(ArchivedField::Sequence),
+            "timestamp" => Some(ArchivedField::Timestamp),
+            field => T::Archived::accessor(field).map(ArchivedField::Payload),
+        })
+        .collect();
+
+    let mut folded: Vec<f64> = reads.iter().map(|r| r.fold.empty()).collect();
+    for point in points {
+        for ((read, field), folded) in reads.iter().zip(fields.iter()).zip(folded.iter_mut()) {
+            let value = match field {
+                Some(ArchivedField::Sequence) => point.sequence.to_native() as f64,
+                Some(ArchivedField::Timestamp) => point.timestamp.to_native() as f64,
+                Some(ArchivedField::Payload(accessor)) => accessor(&point.payload),
+                None => continue,
+            };
+            *folded = read.fold.step(*folded, value);
+        }
+    }
+
+    folded
+}
+
+fn points<T: DeserializeOwned>(payload: &[Payload]) -> Result<Vec<Point<T>>, Error> {
+    payload
+        .iter()
+        .map(|p| {
+            Ok(Point {
+                sequence: p.sequence,
+                timestamp: p.timestamp,
+                payload: T::deserialize(&p.payload)?,
+            })
+        })
+        .collect()
+}
+
+fn payloads<T: Serialize>(points: Vec<Point<T>>) -> Result<Vec<Payload>, Error> {
+    points
+        .into_iter()
+        .map(|p| {
+            Ok(Payload {
+                stream: String::new(),
+                sequence: p.sequence,
+                timestamp: p.timestamp,
+                payload: serde_json::to_value(p.payload)?,
+            })
+        })
+        .collect()
+}
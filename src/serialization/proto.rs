@@ -1,258 +1,452 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::OnceLock;
+
 use prost::Message;
+use prost_reflect::{
+    DescriptorPool, DynamicMessage, FieldDescriptor, Kind, MessageDescriptor, Value as ReflectValue,
+};
+use serde_json::{Map, Value};
 
 use crate::base::Payload;
 
-use self::test::{Bms, BmsList, Gps, GpsList, Imu, ImuList, Peripherals, PeripheralsList};
-
-use super::Error;
+use super::{json_type, set_point_field, type_mismatch, Error};
 
-mod test {
-    include!(concat!(env!("OUT_DIR"), "/test.rs"));
+#[macro_use]
+mod streams {
+    include!(concat!(env!("OUT_DIR"), "/proto_streams.rs"));
 }
 
-pub fn serialize(payload: Vec<Payload>, stream: &str) -> Result<Vec<u8>, Error> {
-    let mut buf = vec![];
+/// Number of the `repeated messages` field of every list message
+const MESSAGES_FIELD: u32 = 1;
 
-    match stream {
-        "test.gpsList" => {
-            let messages: Vec<Gps> = payload
-                .iter()
-                .map(|p| Gps {
-                    longitude: p.payload.get("longitude").unwrap().as_f64().unwrap(),
-                    latitude: p.payload.get("latitude").unwrap().as_f64().unwrap(),
-                    timestamp: p.timestamp,
-                    sequence: p.sequence as i32,
-                })
-                .collect();
-            let list = GpsList { messages };
-            list.encode(&mut buf)?;
+/// Descriptors of the messages in `test.proto`, as compiled by build.rs
+pub fn descriptor_pool() -> &'static DescriptorPool {
+    static POOL: OnceLock<DescriptorPool> = OnceLock::new();
+
+    POOL.get_or_init(|| {
+        let file_descriptor_set =
+            include_bytes!(concat!(env!("OUT_DIR"), "/file_descriptor_set.bin"));
+        DescriptorPool::decode(file_descriptor_set.as_ref()).expect("Invalid file descriptor set")
+    })
+}
+
+/// Top level `<stream>List` messages of a descriptor pool by stream, in any package.
+/// build.rs picks the messages prost generates code for by the same rule, and like
+/// it a stream with list messages in several packages is an error
+pub fn list_messages(
+    descriptor_pool: &DescriptorPool,
+) -> Result<HashMap<String, MessageDescriptor>, Error> {
+    let mut lists = HashMap::new();
+    for message in descriptor_pool.all_messages() {
+        if message.parent_message().is_some() {
+            continue;
         }
-        "test.imuList" => {
-            let messages: Vec<Imu> = payload
-                .iter()
-                .map(|p| Imu {
-                    timestamp: p.timestamp,
-                    sequence: p.sequence,
-                    ax: p.payload.get("ax").unwrap().as_f64().unwrap(),
-                    ay: p.payload.get("ay").unwrap().as_f64().unwrap(),
-                    az: p.payload.get("az").unwrap().as_f64().unwrap(),
-                    pitch: p.payload.get("pitch").unwrap().as_f64().unwrap(),
-                    roll: p.payload.get("roll").unwrap().as_f64().unwrap(),
-                    yaw: p.payload.get("yaw").unwrap().as_f64().unwrap(),
-                    magx: p.payload.get("magx").unwrap().as_f64().unwrap(),
-                    magy: p.payload.get("magy").unwrap().as_f64().unwrap(),
-                    magz: p.payload.get("magz").unwrap().as_f64().unwrap(),
-                })
-                .collect();
-            let list = ImuList { messages };
-            list.encode(&mut buf)?;
+        let Some(stream) = message.name().strip_suffix("List") else {
+            continue;
+        };
+        if let Some(other) = lists.insert(stream.to_owned(), message.clone()) {
+            return Err(Error::AmbiguousStream {
+                stream: stream.to_owned(),
+                first: other.full_name().to_owned(),
+                second: message.full_name().to_owned(),
+            });
         }
-        "test.peripheralsList" => {
-            let messages: Vec<Peripherals> = payload
-                .iter()
-                .map(|p| Peripherals {
-                    timestamp: p.timestamp,
-                    sequence: p.sequence as i32,
-                    gps: p.payload.get("gps").unwrap().as_str().unwrap().to_string(),
-                    gsm: p.payload.get("gsm").unwrap().as_str().unwrap().to_string(),
-                    imu: p.payload.get("imu").unwrap().as_str().unwrap().to_string(),
-                    left_indicator: p
-                        .payload
-                        .get("left_indicator")
-                        .unwrap()
-                        .as_str()
-                        .unwrap()
-                        .to_string(),
-                    right_indicator: p
-                        .payload
-                        .get("right_indicator")
-                        .unwrap()
-                        .as_str()
-                        .unwrap()
-                        .to_string(),
-                    left_brake: p
-                        .payload
-                        .get("left_brake")
-                        .unwrap()
-                        .as_str()
-                        .unwrap()
-                        .to_string(),
-                    right_brake: p
-                        .payload
-                        .get("right_brake")
-                        .unwrap()
-                        .as_str()
-                        .unwrap()
-                        .to_string(),
-                    headlamp: p
-                        .payload
-                        .get("headlamp")
-                        .unwrap()
-                        .as_str()
-                        .unwrap()
-                        .to_string(),
-                    horn: p.payload.get("horn").unwrap().as_str().unwrap().to_string(),
-                })
-                .collect();
-            let list = PeripheralsList { messages };
-            list.encode(&mut buf)?;
+    }
+
+    Ok(lists)
+}
+
+/// Compiles a `.proto` file and its imports into descriptors by running protoc,
+/// `$PROTOC` if set
+pub fn compile(path: &Path, includes: &[PathBuf]) -> Result<DescriptorPool, Error> {
+    let protoc = std::env::var_os("PROTOC").unwrap_or_else(|| "protoc".into());
+    // protoc writes descriptor sets only into files, the directory is removed once dropped
+    let out_dir = tempfile::tempdir()?;
+    let descriptor_set = out_dir.path().join("descriptor_set.bin");
+    let mut descriptor_set_out = OsString::from("--descriptor_set_out=");
+    descriptor_set_out.push(&descriptor_set);
+    let mut command = Command::new(&protoc);
+    command.arg("--include_imports").arg(descriptor_set_out);
+    match includes {
+        [] => {
+            let dir = path.parent().filter(|d| !d.as_os_str().is_empty());
+            command.arg("-I").arg(dir.unwrap_or(Path::new(".")));
         }
-        "test.bmsList" => {
-            let messages: Vec<Bms> = payload
-                .iter()
-                .map(|p| Bms {
-                    timestamp: p.timestamp,
-                    sequence: p.sequence as i32,
-                    periodicity_ms: p.payload.get("periodicity_ms").unwrap().as_i64().unwrap()
-                        as i32,
-                    mosfet_temperature: p
-                        .payload
-                        .get("mosfet_temperature")
-                        .unwrap()
-                        .as_f64()
-                        .unwrap(),
-                    ambient_temperature: p
-                        .payload
-                        .get("ambient_temperature")
-                        .unwrap()
-                        .as_f64()
-                        .unwrap(),
-                    mosfet_status: p.payload.get("mosfet_status").unwrap().as_i64().unwrap() as i32,
-                    cell_voltage_count: p
-                        .payload
-                        .get("cell_voltage_count")
-                        .unwrap()
-                        .as_i64()
-                        .unwrap() as i32,
-                    cell_voltage_1: p.payload.get("cell_voltage_1").unwrap().as_f64().unwrap(),
-                    cell_voltage_2: p.payload.get("cell_voltage_2").unwrap().as_f64().unwrap(),
-                    cell_voltage_3: p.payload.get("cell_voltage_3").unwrap().as_f64().unwrap(),
-                    cell_voltage_4: p.payload.get("cell_voltage_4").unwrap().as_f64().unwrap(),
-                    cell_voltage_5: p.payload.get("cell_voltage_5").unwrap().as_f64().unwrap(),
-                    cell_voltage_6: p.payload.get("cell_voltage_6").unwrap().as_f64().unwrap(),
-                    cell_voltage_7: p.payload.get("cell_voltage_7").unwrap().as_f64().unwrap(),
-                    cell_voltage_8: p.payload.get("cell_voltage_8").unwrap().as_f64().unwrap(),
-                    cell_voltage_9: p.payload.get("cell_voltage_9").unwrap().as_f64().unwrap(),
-                    cell_voltage_10: p.payload.get("cell_voltage_10").unwrap().as_f64().unwrap(),
-                    cell_voltage_11: p.payload.get("cell_voltage_11").unwrap().as_f64().unwrap(),
-                    cell_voltage_12: p.payload.get("cell_voltage_12").unwrap().as_f64().unwrap(),
-                    cell_voltage_13: p.payload.get("cell_voltage_13").unwrap().as_f64().unwrap(),
-                    cell_voltage_14: p.payload.get("cell_voltage_14").unwrap().as_f64().unwrap(),
-                    cell_voltage_15: p.payload.get("cell_voltage_15").unwrap().as_f64().unwrap(),
-                    cell_voltage_16: p.payload.get("cell_voltage_16").unwrap().as_f64().unwrap(),
-                    cell_thermistor_count: p
-                        .payload
-                        .get("cell_thermistor_count")
-                        .unwrap()
-                        .as_i64()
-                        .unwrap() as i32,
-                    cell_temp_1: p.payload.get("cell_temp_1").unwrap().as_f64().unwrap(),
-                    cell_temp_2: p.payload.get("cell_temp_2").unwrap().as_f64().unwrap(),
-                    cell_temp_3: p.payload.get("cell_temp_3").unwrap().as_f64().unwrap(),
-                    cell_temp_4: p.payload.get("cell_temp_4").unwrap().as_f64().unwrap(),
-                    cell_temp_5: p.payload.get("cell_temp_5").unwrap().as_f64().unwrap(),
-                    cell_temp_6: p.payload.get("cell_temp_6").unwrap().as_f64().unwrap(),
-                    cell_temp_7: p.payload.get("cell_temp_7").unwrap().as_f64().unwrap(),
-                    cell_temp_8: p.payload.get("cell_temp_8").unwrap().as_f64().unwrap(),
-                    cell_balancing_status: p
-                        .payload
-                        .get("cell_balancing_status")
-                        .unwrap()
-                        .as_i64()
-                        .unwrap() as i32,
-                    pack_voltage: p.payload.get("pack_voltage").unwrap().as_f64().unwrap(),
-                    pack_current: p.payload.get("pack_current").unwrap().as_f64().unwrap(),
-                    pack_soc: p.payload.get("pack_soc").unwrap().as_f64().unwrap(),
-                    pack_soh: p.payload.get("pack_soh").unwrap().as_f64().unwrap(),
-                    pack_sop: p.payload.get("pack_sop").unwrap().as_f64().unwrap(),
-                    pack_cycle_count: p.payload.get("pack_cycle_count").unwrap().as_i64().unwrap(),
-                    pack_available_energy: p
-                        .payload
-                        .get("pack_available_energy")
-                        .unwrap()
-                        .as_i64()
-                        .unwrap(),
-                    pack_consumed_energy: p
-                        .payload
-                        .get("pack_consumed_energy")
-                        .unwrap()
-                        .as_i64()
-                        .unwrap(),
-                    pack_fault: p.payload.get("pack_fault").unwrap().as_i64().unwrap() as i32,
-                    pack_status: p.payload.get("pack_status").unwrap().as_i64().unwrap() as i32,
-                })
-                .collect();
-            let list = BmsList { messages };
-            list.encode(&mut buf)?;
+        includes => {
+            for include in includes {
+                command.arg("-I").arg(include);
+            }
         }
-        _ => {
-            panic!("Couldn't serialize for stream: {}!", stream)
+    }
+
+    let output = command
+        .arg(path)
+        .output()
+        .map_err(|e| Error::Protoc(format!("running {}: {}", protoc.to_string_lossy(), e)))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(Error::Protoc(stderr.trim().to_owned()));
+    }
+
+    Ok(DescriptorPool::decode(
+        std::fs::read(&descriptor_set)?.as_slice(),
+    )?)
+}
+
+/// `messages` field of a list message and the descriptor of its points
+fn messages_field(list: &MessageDescriptor) -> Result<(FieldDescriptor, MessageDescriptor), Error> {
+    if let Some(field) = list.get_field(MESSAGES_FIELD) {
+        if let (Kind::Message(point), true) = (field.kind(), field.is_list()) {
+            return Ok((field, point));
         }
     }
 
-    Ok(buf)
+    Err(Error::UnknownMessage(list.full_name().to_owned()))
+}
+
+/// Encodes a batch as the list message prost generated for its stream
+pub fn serialize(payload: Vec<Payload>, stream: &str) -> Result<Vec<u8>, Error> {
+    with_list_type!(stream, L => {
+        let list = L {
+            messages: payload
+                .iter()
+                .map(|p| PointMessage::from_point(stream, p))
+                .collect::<Result<_, _>>()?,
+        };
+        Ok(list.encode_to_vec())
+    })
 }
 
 pub fn deserialize(payload: &[u8], stream: &str) -> Result<Vec<Payload>, Error> {
-    match stream {
-        "test.gpsList" => {
-            let list: GpsList = Message::decode(payload)?;
-            let payload = list
+    with_list_type!(stream, L => {
+        L::decode(payload)?
             .messages
-            .iter()
-            .map(|g| Payload {
-                sequence: g.sequence as u32,
-                timestamp: g.timestamp,
-                payload: serde_json::json! ({ "longitude": g.longitude, "latitude": g.latitude }),
-                ..Default::default()
-            })
-            .collect();
-            Ok(payload)
+            .into_iter()
+            .map(|m| m.into_point(stream))
+            .collect()
+    })
+}
+
+/// Generated message of a point, implemented by build.rs for the points of every
+/// list message, with each field set from and read into the payload key of the
+/// same name
+trait PointMessage: Sized {
+    fn from_point(stream: &str, point: &Payload) -> Result<Self, Error>;
+
+    fn into_point(self, stream: &str) -> Result<Payload, Error>;
+}
+
+/// Type of a field of a generated message
+trait Scalar: Sized {
+    /// Name of the type in the schema
+    const NAME: &'static str;
+
+    /// `None` if the value doesn't fit the type
+    fn from_value(value: &Value) -> Option<Self>;
+}
+
+macro_rules! scalar {
+    ($ty:ty, $name:literal, $json:ident) => {
+        impl Scalar for $ty {
+            const NAME: &'static str = $name;
+
+            fn from_value(value: &Value) -> Option<$ty> {
+                value.$json().and_then(|v| <$ty>::try_from(v).ok())
+            }
         }
-        "test.imuList" => {
-            let list: ImuList = Message::decode(payload)?;
-            let payload = list
-            .messages
-            .iter()
-            .map(|i| Payload {
-                sequence: i.sequence as u32,
-                timestamp: i.timestamp,
-                payload: serde_json::json! ({ "ax": i.ax, "ay": i.ay, "az": i.az, "roll": i.roll, "pitch": i.pitch, "yaw": i.yaw, "magx": i.magx, "magy": i.magy, "magz": i.magz }),
-                ..Default::default()
-            })
-            .collect();
-            Ok(payload)
+    };
+}
+
+scalar!(i32, "int32", as_i64);
+scalar!(i64, "int64", as_i64);
+scalar!(u32, "uint32", as_u64);
+scalar!(u64, "uint64", as_u64);
+scalar!(f64, "double", as_f64);
+scalar!(bool, "bool", as_bool);
+
+impl Scalar for f32 {
+    const NAME: &'static str = "float";
+
+    fn from_value(value: &Value) -> Option<f32> {
+        let value = value.as_f64()?;
+        (value.abs() <= f32::MAX as f64).then_some(value as f32)
+    }
+}
+
+impl Scalar for String {
+    const NAME: &'static str = "string";
+
+    fn from_value(value: &Value) -> Option<String> {
+        value.as_str().map(str::to_owned)
+    }
+}
+
+/// Value of a point for a field of its generated message
+fn field<T: Scalar>(stream: &str, point: &Payload, name: &str) -> Result<T, Error> {
+    let value = match name {
+        "sequence" => &Value::from(point.sequence),
+        "timestamp" => &Value::from(point.timestamp),
+        name => point.payload.get(name).ok_or_else(|| Error::MissingField {
+            stream: stream.to_owned(),
+            field: name.to_owned(),
+        })?,
+    };
+
+    T::from_value(value).ok_or_else(|| type_mismatch(stream, name, T::NAME, json_type(value)))
+}
+
+/// List message of a batch, whose points are messages with every field set from
+/// the payload key of the same name
+pub fn to_message(
+    stream: &str,
+    list: &MessageDescriptor,
+    payload: &[Payload],
+) -> Result<DynamicMessage, Error> {
+    let (messages, desc) = messages_field(list)?;
+
+    let points = payload
+        .iter()
+        .map(|p| {
+            let mut point = DynamicMessage::new(desc.clone());
+            for field in desc.fields() {
+                let value = field_value(stream, p, &field)?;
+                point.set_field(&field, reflect_value(stream, &field, &value)?);
+            }
+
+            Ok(ReflectValue::Message(point))
+        })
+        .collect::<Result<_, Error>>()?;
+
+    let mut message = DynamicMessage::new(list.clone());
+    message.set_field(&messages, ReflectValue::List(points));
+
+    Ok(message)
+}
+
+/// Points of the list message of a batch, see [`to_message`]
+pub fn from_message(
+    stream: &str,
+    list: &MessageDescriptor,
+    message: &DynamicMessage,
+) -> Result<Vec<Payload>, Error> {
+    let (messages, desc) = messages_field(list)?;
+    let ReflectValue::List(points) = &*message.get_field(&messages) else {
+        return Err(Error::UnknownMessage(list.full_name().to_owned()));
+    };
+
+    points
+        .iter()
+        .map(|point| {
+            let ReflectValue::Message(point) = point else {
+                return Err(Error::UnknownMessage(desc.full_name().to_owned()));
+            };
+
+            let mut read = Payload::default();
+            let mut payload = Map::new();
+            for field in desc.fields() {
+                let value = json_value(stream, &field, &point.get_field(&field))?;
+                set_point_field(stream, &mut read, &mut payload, field.name(), value)?;
+            }
+            read.payload = Value::Object(payload);
+
+            Ok(read)
+        })
+        .collect()
+}
+
+/// Value of a point for a field of its message
+fn field_value(stream: &str, point: &Payload, field: &FieldDescriptor) -> Result<Value, Error> {
+    let value = match field.name() {
+        "sequence" => Value::from(point.sequence),
+        "timestamp" => Value::from(point.timestamp),
+        name => match point.payload.get(name) {
+            Some(value) => value.clone(),
+            None => {
+                return Err(Error::MissingField {
+                    stream: stream.to_owned(),
+                    field: name.to_owned(),
+                })
+            }
+        },
+    };
+
+    Ok(value)
+}
+
+/// Value of a field of a dynamic message, which has to match the field's kind
+fn reflect_value(
+    stream: &str,
+    field: &FieldDescriptor,
+    value: &Value,
+) -> Result<ReflectValue, Error> {
+    let mismatch = || type_mismatch(stream, field.name(), kind_name(field), json_type(value));
+    if field.is_list() || field.is_map() {
+        return Err(mismatch());
+    }
+
+    let reflect = match field.kind() {
+        Kind::Double => value.as_f64().map(ReflectValue::F64),
+        Kind::Float => f32::from_value(value).map(ReflectValue::F32),
+        Kind::Int32 | Kind::Sint32 | Kind::Sfixed32 => {
+            i32::from_value(value).map(ReflectValue::I32)
         }
-        "test.peripheralsList" => {
-            let list: PeripheralsList = Message::decode(payload)?;
-            let payload = list
-            .messages
-            .iter()
-            .map(|p| Payload {
-                sequence: p.sequence as u32,
-                timestamp: p.timestamp,
-                payload: serde_json::json! ({ "gps": p.gps, "gsm": p.gsm, "imu": p.imu, "right_indicator": p.right_indicator, "left_indicator": p.left_indicator, "right_brake": p.right_brake, "left_brake": p.left_brake, "headlamp": p.headlamp, "horn": p.horn }),
-                ..Default::default()
-            })
-            .collect();
-            Ok(payload)
+        Kind::Int64 | Kind::Sint64 | Kind::Sfixed64 => value.as_i64().map(ReflectValue::I64),
+        Kind::Uint32 | Kind::Fixed32 => u32::from_value(value).map(ReflectValue::U32),
+        Kind::Uint64 | Kind::Fixed64 => value.as_u64().map(ReflectValue::U64),
+        Kind::Bool => value.as_bool().map(ReflectValue::Bool),
+        Kind::String => value.as_str().map(|v| ReflectValue::String(v.to_owned())),
+        _ => None,
+    };
+
+    reflect.ok_or_else(mismatch)
+}
+
+fn json_value(stream: &str, field: &FieldDescriptor, value: &ReflectValue) -> Result<Value, Error> {
+    let value = match value {
+        ReflectValue::Bool(v) => Value::from(*v),
+        ReflectValue::I32(v) => Value::from(*v),
+        ReflectValue::I64(v) => Value::from(*v),
+        ReflectValue::U32(v) => Value::from(*v),
+        ReflectValue::U64(v) => Value::from(*v),
+        ReflectValue::F32(v) => Value::from(*v),
+        ReflectValue::F64(v) => Value::from(*v),
+        ReflectValue::String(v) => Value::from(v.as_str()),
+        _ => return Err(unsupported(stream, field)),
+    };
+
+    Ok(value)
+}
+
+/// Error of a field whose type has no counterpart in a point
+fn unsupported(stream: &str, field: &FieldDescriptor) -> Error {
+    type_mismatch(stream, field.name(), "a scalar", kind_name(field))
+}
+
+fn kind_name(field: &FieldDescriptor) -> String {
+    let kind = match field.kind() {
+        Kind::Message(m) => m.full_name().to_owned(),
+        Kind::Enum(e) => e.full_name().to_owned(),
+        kind => format!("{:?}", kind).to_lowercase(),
+    };
+
+    match field.is_list() {
+        true => format!("repeated {}", kind),
+        false => kind,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use prost_reflect::prost_types::{DescriptorProto, FileDescriptorProto};
+
+    use super::*;
+
+    fn file(name: &str, package: &str, messages: Vec<DescriptorProto>) -> FileDescriptorProto {
+        FileDescriptorProto {
+            name: Some(name.to_owned()),
+            package: Some(package.to_owned()),
+            message_type: messages,
+            syntax: Some("proto3".to_owned()),
+            ..Default::default()
         }
-        "test.bmsList" => {
-            let list: BmsList = Message::decode(payload)?;
-            let payload = list
-                .messages
-                .iter()
-                .map(|b| Payload {
-                    sequence: b.sequence as u32,
-                    timestamp: b.timestamp,
-                    payload: serde_json::json! ({ "periodicity_ms": b.periodicity_ms, "mosfet_temperature": b.mosfet_temperature, "ambient_temperature": b.ambient_temperature, "mosfet_status": b.mosfet_status, "cell_voltage_count": b.cell_voltage_count }),
-                    ..Default::default()
-                })
-                .collect();
-            Ok(payload)
+    }
+
+    fn message(name: &str, nested: Vec<DescriptorProto>) -> DescriptorProto {
+        DescriptorProto {
+            name: Some(name.to_owned()),
+            nested_type: nested,
+            ..Default::default()
         }
-        _ => {
-            panic!("Couldn't deserialize for stream: {}!", stream)
+    }
+
+    #[test]
+    fn every_list_message_has_a_generated_type() {
+        let lists = list_messages(descriptor_pool()).unwrap();
+        assert!(!lists.is_empty());
+        for stream in lists.keys() {
+            let serialized = serialize(vec![], stream).unwrap();
+            assert!(deserialize(&serialized, stream).unwrap().is_empty());
         }
     }
+
+    fn motor(payload: Value) -> Vec<Payload> {
+        vec![Payload {
+            sequence: 1,
+            timestamp: 2,
+            payload,
+            ..Default::default()
+        }]
+    }
+
+    #[test]
+    fn generated_messages_report_missing_and_mismatched_fields() {
+        let fields = serde_json::json!({
+            "temperature1": 1.0,
+            "temperature2": 2.0,
+            "temperature3": 3.0,
+            "voltage": 4.0,
+            "current": 5.0,
+        });
+        let Err(Error::MissingField { field, .. }) = serialize(motor(fields.clone()), "motor")
+        else {
+            panic!("motor without rpm serialized");
+        };
+        assert_eq!(field, "rpm");
+
+        let mut out_of_range = fields;
+        out_of_range["rpm"] = Value::from(u64::from(u32::MAX) + 1);
+        let Err(Error::TypeMismatch {
+            field, expected, ..
+        }) = serialize(motor(out_of_range.clone()), "motor")
+        else {
+            panic!("rpm out of the range of uint32 serialized");
+        };
+        assert_eq!((field.as_str(), expected.as_str()), ("rpm", "uint32"));
+
+        out_of_range["rpm"] = Value::from(3000);
+        let batch = motor(out_of_range);
+        let serialized = serialize(batch.clone(), "motor").unwrap();
+        assert_eq!(deserialize(&serialized, "motor").unwrap(), batch);
+    }
+
+    #[test]
+    fn nested_list_messages_are_not_streams() {
+        let mut pool = DescriptorPool::new();
+        pool.add_file_descriptor_proto(file(
+            "a.proto",
+            "a",
+            vec![message("GpsList", vec![message("ImuList", vec![])])],
+        ))
+        .unwrap();
+
+        let lists = list_messages(&pool).unwrap();
+        assert_eq!(lists.keys().collect::<Vec<_>>(), ["Gps"]);
+        assert_eq!(lists["Gps"].full_name(), "a.GpsList");
+    }
+
+    #[test]
+    fn list_messages_in_several_packages_are_ambiguous() {
+        let mut pool = DescriptorPool::new();
+        pool.add_file_descriptor_protos([
+            file("a.proto", "a", vec![message("GpsList", vec![])]),
+            file("b.proto", "b", vec![message("GpsList", vec![])]),
+        ])
+        .unwrap();
+
+        let Err(Error::AmbiguousStream {
+            stream,
+            first,
+            second,
+        }) = list_messages(&pool)
+        else {
+            panic!("list messages in two packages picked for one stream");
+        };
+        assert_eq!(stream, "Gps");
+        let mut names = [first, second];
+        names.sort();
+        assert_eq!(names, ["a.GpsList", "b.GpsList"]);
+    }
 }
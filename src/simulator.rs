@@ -1,5 +1,5 @@
 use flume::Sender;
-use log::{error, info};
+use log::info;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use thiserror::Error;
@@ -9,9 +9,11 @@ use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use std::{cmp::Ordering, fs, io, sync::Arc};
 
 use crate::base::Buffer;
-use crate::{Payload, SimulatorConfig, Stream, MAX_BUF_SIZE};
+use crate::{Payload, SimulatorConfig, Stream};
 
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rkyv::Archive;
 
 #[derive(Error, Debug)]
 pub enum Error {
@@ -21,12 +23,14 @@ pub enum Error {
     Json(#[from] serde_json::error::Error),
     #[error("flume error {0}")]
     Recv(#[from] flume::RecvError),
+    #[error("Send error {0}")]
+    Send(#[from] crate::base::Error),
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Archive, rkyv::Serialize, rkyv::Deserialize)]
 pub struct Location {
-    latitude: f64,
-    longitude: f64,
+    pub(crate) latitude: f64,
+    pub(crate) longitude: f64,
 }
 
 #[derive(Clone)]
@@ -41,12 +45,43 @@ pub struct DeviceData {
 pub enum DataEventType {
     GenerateGPS,
     GenerateIMU,
-    // GenerateVehicleData,
+    GenerateVehicleData,
     GeneratePeripheralData,
-    // GenerateMotor,
+    GenerateMotor,
+    GenerateCAN,
     GenerateBMS,
 }
 
+impl DataEventType {
+    pub const ALL: [DataEventType; 7] = [
+        DataEventType::GenerateGPS,
+        DataEventType::GenerateVehicleData,
+        DataEventType::GeneratePeripheralData,
+        DataEventType::GenerateMotor,
+        DataEventType::GenerateCAN,
+        DataEventType::GenerateBMS,
+        DataEventType::GenerateIMU,
+    ];
+
+    /// Event generating data of the named stream
+    pub fn from_stream(stream: &str) -> Option<DataEventType> {
+        Self::ALL.into_iter().find(|e| e.stream() == stream)
+    }
+
+    /// Name of the stream onto which data generated by this event is pushed
+    pub fn stream(&self) -> &'static str {
+        match self {
+            DataEventType::GenerateGPS => "gps",
+            DataEventType::GenerateIMU => "imu",
+            DataEventType::GenerateVehicleData => "shadow",
+            DataEventType::GeneratePeripheralData => "peripherals",
+            DataEventType::GenerateMotor => "motor",
+            DataEventType::GenerateCAN => "can",
+            DataEventType::GenerateBMS => "bms",
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct DataEvent {
     timestamp: Instant,
@@ -106,36 +141,63 @@ impl PartialOrd for Event {
     }
 }
 
+/// Unix time in milliseconds at which seeded simulations start, 2020-01-01T00:00:00Z
+const SEEDED_EPOCH_MS: u64 = 1_577_836_800_000;
+
+/// Maps instants of simulated events onto unix timestamps of generated points,
+/// so that timestamps only depend on when an event was scheduled
+#[derive(Debug, Clone, Copy)]
+pub struct Clock {
+    start: Instant,
+    epoch_ms: u64,
+}
+
+impl Clock {
+    pub fn new(start: Instant, seed: Option<u64>) -> Clock {
+        let epoch_ms = match seed {
+            Some(_) => SEEDED_EPOCH_MS,
+            None => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_millis() as u64,
+        };
+
+        Clock { start, epoch_ms }
+    }
+
+    pub fn timestamp(&self, at: Instant) -> u64 {
+        self.epoch_ms + at.duration_since(self.start).as_millis() as u64
+    }
+}
+
+/// Streams are partitioned by name and batch size, every point is
+/// filled into a stream for each of the configured batch sizes
 pub struct Partitions {
-    map: HashMap<String, Stream>,
+    map: HashMap<(String, usize), Stream>,
     tx: Sender<Buffer<Payload>>,
+    batch_sizes: Vec<usize>,
 }
 
 impl Partitions {
-    async fn send(&mut self, payload: Payload) {
-        if let Err(e) = self
-            .map
-            .entry(payload.stream.clone())
-            .or_insert(Stream::new(
-                &payload.stream,
-                &payload.stream,
-                MAX_BUF_SIZE,
-                self.tx.clone(),
-            ))
-            .fill(payload)
-            .await
-        {
-            error!("Failed to send action result {:?}", e);
+    async fn send(&mut self, payload: Payload) -> Result<(), Error> {
+        for &batch_size in self.batch_sizes.iter() {
+            self.map
+                .entry((payload.stream.clone(), batch_size))
+                .or_insert(Stream::new(
+                    &payload.stream,
+                    &payload.stream,
+                    batch_size,
+                    self.tx.clone(),
+                ))
+                .fill(payload.clone())
+                .await?;
         }
+
+        Ok(())
     }
 }
 
-pub fn generate_gps_data(device: &DeviceData, sequence: u32) -> Payload {
-    let timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_millis() as u64;
-
+pub fn generate_gps_data(device: &DeviceData, sequence: u32, timestamp: u64) -> Payload {
     let path_len = device.path.len() as u32;
     let path_index = ((device.path_offset + sequence) % path_len) as usize;
     let position = device.path.get(path_index).unwrap();
@@ -148,114 +210,108 @@ pub fn generate_gps_data(device: &DeviceData, sequence: u32) -> Payload {
     };
 }
 
-pub fn generate_float(start: f64, end: f64) -> f64 {
-    let mut rng = rand::thread_rng();
-
+pub fn generate_float(rng: &mut impl Rng, start: f64, end: f64) -> f64 {
     rng.gen_range(start..end)
 }
 
-pub fn generate_int(start: i32, end: i32) -> i64 {
-    rand::thread_rng().gen_range(start..end) as i64
+pub fn generate_int(rng: &mut impl Rng, start: i32, end: i32) -> i64 {
+    rng.gen_range(start..end) as i64
 }
 
-pub fn generate_bool_string(p: f64) -> String {
-    if rand::thread_rng().gen_bool(p) {
+pub fn generate_bool_string(rng: &mut impl Rng, p: f64) -> String {
+    if rng.gen_bool(p) {
         "on".to_owned()
     } else {
         "off".to_owned()
     }
 }
 
-#[derive(Debug, Serialize)]
-struct Bms {
-    periodicity_ms: i32,
-    mosfet_temperature: f64,
-    ambient_temperature: f64,
-    mosfet_status: i32,
-    cell_voltage_count: i32,
-    cell_voltage_1: f64,
-    cell_voltage_2: f64,
-    cell_voltage_3: f64,
-    cell_voltage_4: f64,
-    cell_voltage_5: f64,
-    cell_voltage_6: f64,
-    cell_voltage_7: f64,
-    cell_voltage_8: f64,
-    cell_voltage_9: f64,
-    cell_voltage_10: f64,
-    cell_voltage_11: f64,
-    cell_voltage_12: f64,
-    cell_voltage_13: f64,
-    cell_voltage_14: f64,
-    cell_voltage_15: f64,
-    cell_voltage_16: f64,
-    cell_thermistor_count: i32,
-    cell_temp_1: f64,
-    cell_temp_2: f64,
-    cell_temp_3: f64,
-    cell_temp_4: f64,
-    cell_temp_5: f64,
-    cell_temp_6: f64,
-    cell_temp_7: f64,
-    cell_temp_8: f64,
-    cell_balancing_status: i32,
-    pack_voltage: f64,
-    pack_current: f64,
-    pack_soc: f64,
-    pack_soh: f64,
-    pack_sop: f64,
-    pack_cycle_count: i64,
-    pack_available_energy: i64,
-    pack_consumed_energy: i64,
-    pack_fault: i32,
-    pack_status: i32,
-}
-
-pub fn generate_bms_data(sequence: u32) -> Payload {
-    let timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_millis() as u64;
+#[derive(Debug, Serialize, Deserialize, Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct Bms {
+    pub(crate) periodicity_ms: i32,
+    pub(crate) mosfet_temperature: f64,
+    pub(crate) ambient_temperature: f64,
+    pub(crate) mosfet_status: i32,
+    pub(crate) cell_voltage_count: i32,
+    pub(crate) cell_voltage_1: f64,
+    pub(crate) cell_voltage_2: f64,
+    pub(crate) cell_voltage_3: f64,
+    pub(crate) cell_voltage_4: f64,
+    pub(crate) cell_voltage_5: f64,
+    pub(crate) cell_voltage_6: f64,
+    pub(crate) cell_voltage_7: f64,
+    pub(crate) cell_voltage_8: f64,
+    pub(crate) cell_voltage_9: f64,
+    pub(crate) cell_voltage_10: f64,
+    pub(crate) cell_voltage_11: f64,
+    pub(crate) cell_voltage_12: f64,
+    pub(crate) cell_voltage_13: f64,
+    pub(crate) cell_voltage_14: f64,
+    pub(crate) cell_voltage_15: f64,
+    pub(crate) cell_voltage_16: f64,
+    pub(crate) cell_thermistor_count: i32,
+    pub(crate) cell_temp_1: f64,
+    pub(crate) cell_temp_2: f64,
+    pub(crate) cell_temp_3: f64,
+    pub(crate) cell_temp_4: f64,
+    pub(crate) cell_temp_5: f64,
+    pub(crate) cell_temp_6: f64,
+    pub(crate) cell_temp_7: f64,
+    pub(crate) cell_temp_8: f64,
+    pub(crate) cell_balancing_status: i32,
+    pub(crate) pack_voltage: f64,
+    pub(crate) pack_current: f64,
+    pub(crate) pack_soc: f64,
+    pub(crate) pack_soh: f64,
+    pub(crate) pack_sop: f64,
+    pub(crate) pack_cycle_count: i64,
+    pub(crate) pack_available_energy: i64,
+    pub(crate) pack_consumed_energy: i64,
+    pub(crate) pack_fault: i32,
+    pub(crate) pack_status: i32,
+}
+
+pub fn generate_bms_data(rng: &mut impl Rng, sequence: u32, timestamp: u64) -> Payload {
     let payload = Bms {
         periodicity_ms: 250,
-        mosfet_temperature: generate_float(40f64, 45f64),
-        ambient_temperature: generate_float(35f64, 40f64),
+        mosfet_temperature: generate_float(rng, 40f64, 45f64),
+        ambient_temperature: generate_float(rng, 35f64, 40f64),
         mosfet_status: 1,
         cell_voltage_count: 16,
-        cell_voltage_1: generate_float(3.0f64, 3.2f64),
-        cell_voltage_2: generate_float(3.0f64, 3.2f64),
-        cell_voltage_3: generate_float(3.0f64, 3.2f64),
-        cell_voltage_4: generate_float(3.0f64, 3.2f64),
-        cell_voltage_5: generate_float(3.0f64, 3.2f64),
-        cell_voltage_6: generate_float(3.0f64, 3.2f64),
-        cell_voltage_7: generate_float(3.0f64, 3.2f64),
-        cell_voltage_8: generate_float(3.0f64, 3.2f64),
-        cell_voltage_9: generate_float(3.0f64, 3.2f64),
-        cell_voltage_10: generate_float(3.0f64, 3.2f64),
-        cell_voltage_11: generate_float(3.0f64, 3.2f64),
-        cell_voltage_12: generate_float(3.0f64, 3.2f64),
-        cell_voltage_13: generate_float(3.0f64, 3.2f64),
-        cell_voltage_14: generate_float(3.0f64, 3.2f64),
-        cell_voltage_15: generate_float(3.0f64, 3.2f64),
-        cell_voltage_16: generate_float(3.0f64, 3.2f64),
+        cell_voltage_1: generate_float(rng, 3.0f64, 3.2f64),
+        cell_voltage_2: generate_float(rng, 3.0f64, 3.2f64),
+        cell_voltage_3: generate_float(rng, 3.0f64, 3.2f64),
+        cell_voltage_4: generate_float(rng, 3.0f64, 3.2f64),
+        cell_voltage_5: generate_float(rng, 3.0f64, 3.2f64),
+        cell_voltage_6: generate_float(rng, 3.0f64, 3.2f64),
+        cell_voltage_7: generate_float(rng, 3.0f64, 3.2f64),
+        cell_voltage_8: generate_float(rng, 3.0f64, 3.2f64),
+        cell_voltage_9: generate_float(rng, 3.0f64, 3.2f64),
+        cell_voltage_10: generate_float(rng, 3.0f64, 3.2f64),
+        cell_voltage_11: generate_float(rng, 3.0f64, 3.2f64),
+        cell_voltage_12: generate_float(rng, 3.0f64, 3.2f64),
+        cell_voltage_13: generate_float(rng, 3.0f64, 3.2f64),
+        cell_voltage_14: generate_float(rng, 3.0f64, 3.2f64),
+        cell_voltage_15: generate_float(rng, 3.0f64, 3.2f64),
+        cell_voltage_16: generate_float(rng, 3.0f64, 3.2f64),
         cell_thermistor_count: 8,
-        cell_temp_1: generate_float(40.0f64, 43.0f64),
-        cell_temp_2: generate_float(40.0f64, 43.0f64),
-        cell_temp_3: generate_float(40.0f64, 43.0f64),
-        cell_temp_4: generate_float(40.0f64, 43.0f64),
-        cell_temp_5: generate_float(40.0f64, 43.0f64),
-        cell_temp_6: generate_float(40.0f64, 43.0f64),
-        cell_temp_7: generate_float(40.0f64, 43.0f64),
-        cell_temp_8: generate_float(40.0f64, 43.0f64),
+        cell_temp_1: generate_float(rng, 40.0f64, 43.0f64),
+        cell_temp_2: generate_float(rng, 40.0f64, 43.0f64),
+        cell_temp_3: generate_float(rng, 40.0f64, 43.0f64),
+        cell_temp_4: generate_float(rng, 40.0f64, 43.0f64),
+        cell_temp_5: generate_float(rng, 40.0f64, 43.0f64),
+        cell_temp_6: generate_float(rng, 40.0f64, 43.0f64),
+        cell_temp_7: generate_float(rng, 40.0f64, 43.0f64),
+        cell_temp_8: generate_float(rng, 40.0f64, 43.0f64),
         cell_balancing_status: 1,
-        pack_voltage: generate_float(95f64, 96f64),
-        pack_current: generate_float(15f64, 20f64),
-        pack_soc: generate_float(80f64, 90f64),
-        pack_soh: generate_float(9.5f64, 9.9f64),
-        pack_sop: generate_float(9.5f64, 9.9f64),
-        pack_cycle_count: generate_int(100, 150),
-        pack_available_energy: generate_int(2000, 3000),
-        pack_consumed_energy: generate_int(2000, 3000),
+        pack_voltage: generate_float(rng, 95f64, 96f64),
+        pack_current: generate_float(rng, 15f64, 20f64),
+        pack_soc: generate_float(rng, 80f64, 90f64),
+        pack_soh: generate_float(rng, 9.5f64, 9.9f64),
+        pack_sop: generate_float(rng, 9.5f64, 9.9f64),
+        pack_cycle_count: generate_int(rng, 100, 150),
+        pack_available_energy: generate_int(rng, 2000, 3000),
+        pack_consumed_energy: generate_int(rng, 2000, 3000),
         pack_fault: 0,
         pack_status: 1,
     };
@@ -268,34 +324,30 @@ pub fn generate_bms_data(sequence: u32) -> Payload {
     };
 }
 
-#[derive(Debug, Serialize)]
-struct Imu {
-    ax: f64,
-    ay: f64,
-    az: f64,
-    pitch: f64,
-    roll: f64,
-    yaw: f64,
-    magx: f64,
-    magy: f64,
-    magz: f64,
-}
-
-pub fn generate_imu_data(sequence: u32) -> Payload {
-    let timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_millis() as u64;
+#[derive(Debug, Serialize, Deserialize, Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct Imu {
+    pub(crate) ax: f64,
+    pub(crate) ay: f64,
+    pub(crate) az: f64,
+    pub(crate) pitch: f64,
+    pub(crate) roll: f64,
+    pub(crate) yaw: f64,
+    pub(crate) magx: f64,
+    pub(crate) magy: f64,
+    pub(crate) magz: f64,
+}
+
+pub fn generate_imu_data(rng: &mut impl Rng, sequence: u32, timestamp: u64) -> Payload {
     let payload = Imu {
-        ax: generate_float(1f64, 2.8f64),
-        ay: generate_float(1f64, 2.8f64),
-        az: generate_float(9.79f64, 9.82f64),
-        pitch: generate_float(0.8f64, 1f64),
-        roll: generate_float(0.8f64, 1f64),
-        yaw: generate_float(0.8f64, 1f64),
-        magx: generate_float(-45f64, -15f64),
-        magy: generate_float(-45f64, -15f64),
-        magz: generate_float(-45f64, -15f64),
+        ax: generate_float(rng, 1f64, 2.8f64),
+        ay: generate_float(rng, 1f64, 2.8f64),
+        az: generate_float(rng, 9.79f64, 9.82f64),
+        pitch: generate_float(rng, 0.8f64, 1f64),
+        roll: generate_float(rng, 0.8f64, 1f64),
+        yaw: generate_float(rng, 0.8f64, 1f64),
+        magx: generate_float(rng, -45f64, -15f64),
+        magy: generate_float(rng, -45f64, -15f64),
+        magz: generate_float(rng, -45f64, -15f64),
     };
 
     return Payload {
@@ -306,40 +358,55 @@ pub fn generate_imu_data(sequence: u32) -> Payload {
     };
 }
 
-// #[derive(Debug, Serialize)]
-// struct Motor {
-//     temperature1: f64,
-//     temperature2: f64,
-//     temperature3: f64,
-//     voltage: f64,
-//     current: f64,
-//     rpm: i64,
-// }
-
-// pub fn generate_motor_data(sequence: u32) -> Payload {
-//     let timestamp = SystemTime::now()
-//         .duration_since(UNIX_EPOCH)
-//         .unwrap()
-//         .as_millis() as u64;
-//     let payload = Motor {
-//         temperature1: generate_float(40f64, 45f64),
-//         temperature2: generate_float(40f64, 45f64),
-//         temperature3: generate_float(40f64, 45f64),
-//         voltage: generate_float(95f64, 96f64),
-//         current: generate_float(20f64, 25f64),
-//         rpm: generate_int(1000, 9000),
-//     };
-
-//     return Payload {
-//         timestamp,
-//         sequence,
-//         stream: "motor".to_string(),
-//         payload: json!(payload),
-//     };
-// }
-
-#[derive(Debug, Serialize)]
-struct Peripheral {
+#[derive(Debug, Serialize, Deserialize, Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct Motor {
+    pub(crate) temperature1: f64,
+    pub(crate) temperature2: f64,
+    pub(crate) temperature3: f64,
+    pub(crate) voltage: f64,
+    pub(crate) current: f64,
+    pub(crate) rpm: i64,
+}
+
+pub fn generate_motor_data(rng: &mut impl Rng, sequence: u32, timestamp: u64) -> Payload {
+    let payload = Motor {
+        temperature1: generate_float(rng, 40f64, 45f64),
+        temperature2: generate_float(rng, 40f64, 45f64),
+        temperature3: generate_float(rng, 40f64, 45f64),
+        voltage: generate_float(rng, 95f64, 96f64),
+        current: generate_float(rng, 20f64, 25f64),
+        rpm: generate_int(rng, 1000, 9000),
+    };
+
+    return Payload {
+        timestamp,
+        sequence,
+        stream: "motor".to_string(),
+        payload: json!(payload),
+    };
+}
+
+#[derive(Debug, Serialize, Deserialize, Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct Can {
+    pub(crate) data: u64,
+}
+
+pub fn generate_can_data(rng: &mut impl Rng, sequence: u32, timestamp: u64) -> Payload {
+    // the 8 bytes of a frame, kept within an i64 for formats without unsigned 64 bit integers
+    let payload = Can {
+        data: rng.gen_range(0..=i64::MAX as u64),
+    };
+
+    return Payload {
+        timestamp,
+        sequence,
+        stream: "can".to_string(),
+        payload: json!(payload),
+    };
+}
+
+#[derive(Debug, Serialize, Deserialize, Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct Peripheral {
     gps: String,
     gsm: String,
     imu: String,
@@ -351,21 +418,21 @@ struct Peripheral {
     right_brake: String,
 }
 
-pub fn generate_peripheral_state_data(sequence: u32) -> Payload {
-    let timestamp = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap()
-        .as_millis() as u64;
+pub fn generate_peripheral_state_data(
+    rng: &mut impl Rng,
+    sequence: u32,
+    timestamp: u64,
+) -> Payload {
     let payload = Peripheral {
-        gps: generate_bool_string(0.99),
-        gsm: generate_bool_string(0.99),
-        imu: generate_bool_string(0.99),
-        left_indicator: generate_bool_string(0.1),
-        right_indicator: generate_bool_string(0.1),
-        headlamp: generate_bool_string(0.9),
-        horn: generate_bool_string(0.05),
-        left_brake: generate_bool_string(0.1),
-        right_brake: generate_bool_string(0.1),
+        gps: generate_bool_string(rng, 0.99),
+        gsm: generate_bool_string(rng, 0.99),
+        imu: generate_bool_string(rng, 0.99),
+        left_indicator: generate_bool_string(rng, 0.1),
+        right_indicator: generate_bool_string(rng, 0.1),
+        headlamp: generate_bool_string(rng, 0.9),
+        horn: generate_bool_string(rng, 0.05),
+        left_brake: generate_bool_string(rng, 0.1),
+        right_brake: generate_bool_string(rng, 0.1),
     };
 
     return Payload {
@@ -376,40 +443,35 @@ pub fn generate_peripheral_state_data(sequence: u32) -> Payload {
     };
 }
 
-// #[derive(Debug, Serialize)]
-// struct DeviceShadow {
-//     mode: String,
-//     status: String,
-//     firmware_version: String,
-//     config_version: String,
-//     distance_travelled: i64,
-//     range: i64,
-//     #[serde(rename(serialize = "SOC"))]
-//     soc: f64,
-// }
-
-// pub fn generate_device_shadow_data(sequence: u32) -> Payload {
-//     let timestamp = SystemTime::now()
-//         .duration_since(UNIX_EPOCH)
-//         .unwrap()
-//         .as_millis() as u64;
-//     let payload = DeviceShadow {
-//         mode: "economy".to_owned(),
-//         status: "Locked".to_owned(),
-//         firmware_version: "1.33-Aug-2020b1".to_owned(),
-//         config_version: "1.23".to_owned(),
-//         distance_travelled: generate_int(20000, 30000),
-//         range: generate_int(50000, 60000),
-//         soc: generate_float(50f64, 90f64),
-//     };
-
-//     return Payload {
-//         timestamp,
-//         sequence,
-//         stream: "shadow".to_string(),
-//         payload: json!(payload),
-//     };
-// }
+#[derive(Debug, Serialize, Deserialize, Archive, rkyv::Serialize, rkyv::Deserialize)]
+pub struct DeviceShadow {
+    pub(crate) mode: String,
+    pub(crate) status: String,
+    pub(crate) firmware_version: String,
+    pub(crate) config_version: String,
+    pub(crate) distance_travelled: i64,
+    pub(crate) range: i64,
+    pub(crate) soc: f64,
+}
+
+pub fn generate_device_shadow_data(rng: &mut impl Rng, sequence: u32, timestamp: u64) -> Payload {
+    let payload = DeviceShadow {
+        mode: "economy".to_owned(),
+        status: "Locked".to_owned(),
+        firmware_version: "1.33-Aug-2020b1".to_owned(),
+        config_version: "1.23".to_owned(),
+        distance_travelled: generate_int(rng, 20000, 30000),
+        range: generate_int(rng, 50000, 60000),
+        soc: generate_float(rng, 50f64, 90f64),
+    };
+
+    return Payload {
+        timestamp,
+        sequence,
+        stream: "shadow".to_string(),
+        payload: json!(payload),
+    };
+}
 
 pub fn read_gps_paths(paths_dir: &str) -> Vec<Arc<Vec<Location>>> {
     (0..10)
@@ -425,9 +487,11 @@ pub fn read_gps_paths(paths_dir: &str) -> Vec<Arc<Vec<Location>>> {
         .collect::<Vec<_>>()
 }
 
-pub fn new_device_data(device_id: u32, paths: &[Arc<Vec<Location>>]) -> DeviceData {
-    let mut rng = rand::thread_rng();
-
+pub fn new_device_data(
+    rng: &mut impl Rng,
+    device_id: u32,
+    paths: &[Arc<Vec<Location>>],
+) -> DeviceData {
     let n = rng.gen_range(0..10);
     let path = paths.get(n).unwrap().clone();
     let path_index = rng.gen_range(0..path.len()) as u32;
@@ -444,51 +508,19 @@ pub fn generate_initial_events(
     events: &mut BinaryHeap<Event>,
     timestamp: Instant,
     devices: &[DeviceData],
+    event_types: &[DataEventType],
 ) {
     for device in devices.iter() {
         let timestamp = timestamp + device.time_offset;
 
-        events.push(Event::DataEvent(DataEvent {
-            event_type: DataEventType::GenerateGPS,
-            device: device.clone(),
-            timestamp,
-            sequence: 1,
-        }));
-
-        // events.push(Event::DataEvent(DataEvent {
-        //     event_type: DataEventType::GenerateVehicleData,
-        //     device: device.clone(),
-        //     timestamp,
-        //     sequence: 1,
-        // }));
-
-        events.push(Event::DataEvent(DataEvent {
-            event_type: DataEventType::GeneratePeripheralData,
-            device: device.clone(),
-            timestamp,
-            sequence: 1,
-        }));
-
-        // events.push(Event::DataEvent(DataEvent {
-        //     event_type: DataEventType::GenerateMotor,
-        //     device: device.clone(),
-        //     timestamp,
-        //     sequence: 1,
-        // }));
-
-        events.push(Event::DataEvent(DataEvent {
-            event_type: DataEventType::GenerateBMS,
-            device: device.clone(),
-            timestamp,
-            sequence: 1,
-        }));
-
-        events.push(Event::DataEvent(DataEvent {
-            event_type: DataEventType::GenerateIMU,
-            device: device.clone(),
-            timestamp,
-            sequence: 1,
-        }));
+        for event_type in event_types {
+            events.push(Event::DataEvent(DataEvent {
+                event_type: *event_type,
+                device: device.clone(),
+                timestamp,
+                sequence: 1,
+            }));
+        }
     }
 }
 
@@ -496,9 +528,10 @@ pub fn next_event_duration(event_type: DataEventType) -> Duration {
     match event_type {
         DataEventType::GenerateGPS => Duration::from_millis(1000),
         DataEventType::GenerateIMU => Duration::from_millis(100),
-        // DataEventType::GenerateVehicleData => Duration::from_millis(1000),
+        DataEventType::GenerateVehicleData => Duration::from_millis(1000),
         DataEventType::GeneratePeripheralData => Duration::from_millis(1000),
-        // DataEventType::GenerateMotor => Duration::from_millis(250),
+        DataEventType::GenerateMotor => Duration::from_millis(250),
+        DataEventType::GenerateCAN => Duration::from_millis(100),
         DataEventType::GenerateBMS => Duration::from_millis(250),
     }
 }
@@ -507,17 +540,24 @@ pub async fn process_data_event(
     event: &DataEvent,
     events: &mut BinaryHeap<Event>,
     partitions: &mut Partitions,
-) {
+    rng: &mut StdRng,
+    clock: &Clock,
+) -> Result<(), Error> {
+    let timestamp = clock.timestamp(event.timestamp);
+    let sequence = event.sequence;
     let data = match event.event_type {
-        DataEventType::GenerateGPS => generate_gps_data(&event.device, event.sequence),
-        DataEventType::GenerateIMU => generate_imu_data(event.sequence),
-        // DataEventType::GenerateVehicleData => generate_device_shadow_data(event.sequence),
-        DataEventType::GeneratePeripheralData => generate_peripheral_state_data(event.sequence),
-        // DataEventType::GenerateMotor => generate_motor_data(event.sequence),
-        DataEventType::GenerateBMS => generate_bms_data(event.sequence),
+        DataEventType::GenerateGPS => generate_gps_data(&event.device, sequence, timestamp),
+        DataEventType::GenerateIMU => generate_imu_data(rng, sequence, timestamp),
+        DataEventType::GenerateVehicleData => generate_device_shadow_data(rng, sequence, timestamp),
+        DataEventType::GeneratePeripheralData => {
+            generate_peripheral_state_data(rng, sequence, timestamp)
+        }
+        DataEventType::GenerateMotor => generate_motor_data(rng, sequence, timestamp),
+        DataEventType::GenerateCAN => generate_can_data(rng, sequence, timestamp),
+        DataEventType::GenerateBMS => generate_bms_data(rng, sequence, timestamp),
     };
 
-    partitions.send(data).await;
+    partitions.send(data).await?;
 
     let duration = next_event_duration(event.event_type);
 
@@ -527,9 +567,16 @@ pub async fn process_data_event(
         device: event.device.clone(),
         event_type: event.event_type,
     }));
+
+    Ok(())
 }
 
-pub async fn process_events(events: &mut BinaryHeap<Event>, partitions: &mut Partitions) {
+pub async fn process_events(
+    events: &mut BinaryHeap<Event>,
+    partitions: &mut Partitions,
+    rng: &mut StdRng,
+    clock: &Clock,
+) -> Result<(), Error> {
     if let Some(e) = events.pop() {
         // let current_time = Instant::now();
         // let timestamp = event_timestamp(&e);
@@ -544,14 +591,17 @@ pub async fn process_events(events: &mut BinaryHeap<Event>, partitions: &mut Par
 
         match e {
             Event::DataEvent(event) => {
-                process_data_event(&event, events, partitions).await;
+                process_data_event(&event, events, partitions, rng, clock).await?;
             }
         }
     } // else {
     //     tokio::time::sleep(Duration::from_millis(100)).await;
     // }
+
+    Ok(())
 }
 
+/// Simulates devices until every receiver of `data_tx` has been dropped
 #[tokio::main]
 pub async fn start(
     data_tx: Sender<Buffer<Payload>>,
@@ -559,18 +609,29 @@ pub async fn start(
 ) -> Result<(), Error> {
     let paths = read_gps_paths(&simulator_config.gps_paths);
     let num_devices = simulator_config.num_devices;
+    let mut rng = match simulator_config.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
 
     let devices = (1..(num_devices + 1))
-        .map(|i| new_device_data(i, &paths))
+        .map(|i| new_device_data(&mut rng, i, &paths))
+        .collect::<Vec<_>>();
+
+    let event_types = DataEventType::ALL
+        .into_iter()
+        .filter(|e| simulator_config.streams.iter().any(|s| s == e.stream()))
         .collect::<Vec<_>>();
 
     let mut events = BinaryHeap::new();
 
-    generate_initial_events(&mut events, Instant::now(), &devices);
+    let clock = Clock::new(Instant::now(), simulator_config.seed);
+    generate_initial_events(&mut events, clock.start, &devices, &event_types);
 
     let mut partitions = Partitions {
         map: HashMap::new(),
         tx: data_tx,
+        batch_sizes: simulator_config.batch_sizes.clone(),
     };
     let mut time = Instant::now();
     let mut i = 0;
@@ -603,6 +664,87 @@ pub async fn start(
             time = Instant::now();
         }
 
-        process_events(&mut events, &mut partitions).await
+        process_events(&mut events, &mut partitions, &mut rng, &clock).await?;
+    }
+}
+
+/// Points of the golden file, the first point of every stream simulated with seed
+/// 42, each along with the stream it was flushed on
+#[cfg(test)]
+pub(crate) fn golden_points() -> Vec<(String, Payload)> {
+    #[derive(Deserialize)]
+    struct Golden {
+        stream: String,
+        point: Payload,
+    }
+
+    let golden: Vec<Golden> =
+        serde_json::from_str(include_str!("../tests/fixtures/seed_42.json")).unwrap();
+    golden.into_iter().map(|g| (g.stream, g.point)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fidelity::{self, Fidelity};
+
+    /// First `count` buffers of a simulation of two devices with `seed`
+    fn simulate(seed: u64, count: usize) -> Vec<Buffer<Payload>> {
+        let config = SimulatorConfig {
+            num_devices: 2,
+            gps_paths: concat!(env!("CARGO_MANIFEST_DIR"), "/paths").to_owned(),
+            batch_sizes: vec![1, 10],
+            streams: DataEventType::ALL
+                .iter()
+                .map(|e| e.stream().to_owned())
+                .collect(),
+            seed: Some(seed),
+        };
+
+        let (tx, rx) = flume::bounded(10);
+        let simulator = std::thread::spawn(move || start(tx, &config));
+        let buffers: Vec<Buffer<Payload>> = rx.iter().take(count).collect();
+        drop(rx);
+
+        // the simulator stops once there's no one left to send to
+        assert!(matches!(simulator.join().unwrap(), Err(Error::Send(_))));
+
+        buffers
+    }
+
+    #[test]
+    fn same_seed_simulates_same_points() {
+        let first = simulate(42, 200);
+        let second = simulate(42, 200);
+
+        assert_eq!(first.len(), 200);
+        for (a, b) in first.iter().zip(second.iter()) {
+            assert_eq!(a.stream, b.stream);
+            assert_eq!(a.batch_size, b.batch_size);
+            assert_eq!(a.buffer, b.buffer);
+        }
+    }
+
+    #[test]
+    fn seeded_points_match_golden_file() {
+        let golden = golden_points();
+        let simulated = simulate(42, golden.len() * 2);
+        let points: Vec<Payload> = simulated
+            .iter()
+            .filter(|b| b.batch_size == 1)
+            .map(|b| b.buffer[0].clone())
+            .take(golden.len())
+            .collect();
+
+        let streams: Vec<&str> = points.iter().map(|p| p.stream.as_str()).collect();
+        let golden_streams: Vec<&str> = golden.iter().map(|(s, _)| s.as_str()).collect();
+        assert_eq!(streams, golden_streams);
+
+        // json text of the golden file may be parsed a bit off the exact floats
+        let golden: Vec<Payload> = golden.into_iter().map(|(_, point)| point).collect();
+        match fidelity::check(&golden, &points, 1e-12) {
+            Fidelity::Lossless => {}
+            fidelity => panic!("simulated points differ from golden file: {}", fidelity),
+        }
     }
 }